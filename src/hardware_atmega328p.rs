@@ -1,21 +1,889 @@
 //! A hardware implementation of I2C for the ATMega328P.
 
-use crate::{Direction, I2CBus};
+use core::cell::{Cell, RefCell};
+
+use crate::{Direction, I2CBus, SevenBitAddress, TransactionError};
+
+pub mod slave;
+pub mod smbus;
+pub mod trace;
+
+pub use slave::{SlaveHandler, TWISlave, TWISlaveInterrupt};
+pub use smbus::SMBus;
+pub use trace::{TraceBuffer, TraceEvent};
+
+use trace::Trace;
+
+/// The CPU clock frequency in Hz
+pub const F_CPU: u32 = 16_000_000;
 
 /// Represents the hardware 2-wire interface
 pub struct TWI {
     pub twbr: u8,
+    pub line_timing: LineTiming,
+    pub unknown_status_policy: UnknownStatusPolicy,
+    pub max_transfer_len: Option<usize>,
+    pub timeout: Option<u32>,
+    pub own_address: Option<SevenBitAddress>,
+    pub auto_listen: bool,
+    pub clear_pending: bool,
+    #[cfg(feature = "experimental-diagnostics")]
+    pub timer_ns: Option<fn() -> u32>,
+    #[cfg(feature = "transaction-observer")]
+    pub observer: Option<&'static dyn TransactionObserver>,
 }
 
 impl TWI {
     /// Creates a new TWI instance
+    ///
+    /// The bit rate is computed for a prescaler of 1, so the
+    /// frequency saturates to the range reachable with it,
+    /// from about 30.4 kHz to [`max_frequency_hz`]. Lower
+    /// frequencies need a prescaler, see [`with_device_freq`].
+    ///
+    /// [`max_frequency_hz`]: Self::max_frequency_hz
+    /// [`with_device_freq`]: Self::with_device_freq
     pub const fn new(freq_hz: u32) -> Self {
         Self {
-            twbr: (16_000_000 / (2 * freq_hz) - 8) as u8,
+            twbr: clamp_twbr(twbr_for_frequency(freq_hz, 0)),
+            line_timing: LineTiming::STANDARD_MODE,
+            unknown_status_policy: UnknownStatusPolicy::Abort,
+            max_transfer_len: None,
+            timeout: None,
+            own_address: None,
+            auto_listen: false,
+            clear_pending: false,
+            #[cfg(feature = "experimental-diagnostics")]
+            timer_ns: None,
+            #[cfg(feature = "transaction-observer")]
+            observer: None,
+        }
+    }
+
+    /// Sets the timing used when manipulating the bus lines directly
+    pub const fn with_line_timing(mut self, line_timing: LineTiming) -> Self {
+        self.line_timing = line_timing;
+        self
+    }
+
+    /// Sets how unknown TWSR status codes are handled
+    pub const fn with_unknown_status_policy(mut self, policy: UnknownStatusPolicy) -> Self {
+        self.unknown_status_policy = policy;
+        self
+    }
+
+    /// Sets the maximum length of a single `send` or `read`
+    ///
+    /// Longer transfers fail with [`TWIErrorKind::TransferTooLarge`]
+    /// before touching the bus, so a runaway transfer cannot
+    /// monopolize a shared bus. A [`read_with`] fails once it has
+    /// read as many bytes, after releasing the slave. Transfers
+    /// are unbounded by default.
+    ///
+    /// [`read_with`]: I2CBus::read_with
+    pub const fn with_max_transfer_len(mut self, max_transfer_len: usize) -> Self {
+        self.max_transfer_len = Some(max_transfer_len);
+        self
+    }
+
+    /// Sets the number of polls each operation may wait for the hardware
+    ///
+    /// Operations that take longer fail with [`TWIErrorKind::Timeout`].
+    /// Operations wait forever by default.
+    pub const fn with_timeout(mut self, iterations: u32) -> Self {
+        self.timeout = Some(iterations);
+        self
+    }
+
+    /// Sets the own slave address of a combined master/slave node
+    ///
+    /// The address is written to TWAR by [`init`]. Taking a
+    /// [`SevenBitAddress`] ensures it fits in the upper seven
+    /// bits of TWAR; check a plain `u8` with [`SevenBitAddress::new`].
+    ///
+    /// [`init`]: I2CBus::init
+    pub const fn with_own_address(mut self, address: SevenBitAddress) -> Self {
+        self.own_address = Some(address);
+        self
+    }
+
+    /// Sets whether the hardware listens for the own slave
+    /// address whenever it is not performing a master transaction
+    ///
+    /// When set, [`init`] and every stop condition arm slave
+    /// reception, so a combined master/slave node keeps responding
+    /// as a slave after its own master transactions. Incoming
+    /// transactions must then be serviced, e.g. with [`TWISlave`].
+    ///
+    /// [`init`]: I2CBus::init
+    pub const fn with_auto_listen(mut self, auto_listen: bool) -> Self {
+        self.auto_listen = auto_listen;
+        self
+    }
+
+    /// Sets whether [`init`] clears a pending TWINT flag
+    ///
+    /// Writing only TWEN, as [`init`] does by default, leaves a
+    /// TWINT flag set by an earlier operation (e.g. before a soft
+    /// reset) pending. Master operations are not affected, as they
+    /// write TWINT to clear the flag before each wait, but the
+    /// slave side polls it directly: [`is_addressed`] and
+    /// [`TWISlave::receive`] would act on the stale status. When
+    /// set, [`init`] also writes TWINT to clear the flag and start
+    /// from a clean slate.
+    ///
+    /// [`init`]: I2CBus::init
+    /// [`is_addressed`]: TWISlave::is_addressed
+    pub const fn with_clear_pending(mut self, clear_pending: bool) -> Self {
+        self.clear_pending = clear_pending;
+        self
+    }
+
+    /// Returns TWEA if slave reception should be armed while idle
+    const fn listen_mask(&self) -> u8 {
+        if self.auto_listen {
+            TWEA
+        } else {
+            0
+        }
+    }
+
+    /// Returns the TWCR value written by [`init`]
+    ///
+    /// [`init`]: I2CBus::init
+    const fn init_control(&self) -> u8 {
+        let clear = if self.clear_pending { TWINT } else { 0 };
+        TWEN | clear | self.listen_mask()
+    }
+
+    /// Creates a stop condition, re-arming slave reception
+    /// if auto-listen is set
+    fn stop(&self) {
+        if let (true, Some(address)) = (self.auto_listen, self.own_address) {
+            write_register(TWAR, address.get() << 1);
+        }
+        write_register(TWCR, TWINT | TWSTO | TWEN | self.listen_mask());
+    }
+
+    /// Checks `len` against the configured maximum transfer length
+    fn check_transfer_len(&self, len: usize) -> Result<(), TWIError> {
+        match self.max_transfer_len {
+            Some(max) if len > max => Err(TWIErrorKind::TransferTooLarge.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Sets the timer used by diagnostics
+    ///
+    /// `timer_ns` returns a free-running timestamp in
+    /// nanoseconds, which may wrap around.
+    #[cfg(feature = "experimental-diagnostics")]
+    pub const fn with_timer(mut self, timer_ns: fn() -> u32) -> Self {
+        self.timer_ns = Some(timer_ns);
+        self
+    }
+
+    /// Sets the observer notified of the outcome of every
+    /// transaction, see [`TransactionObserver`]
+    #[cfg(feature = "transaction-observer")]
+    pub const fn with_observer(mut self, observer: &'static dyn TransactionObserver) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Measures the rise time of SCL in nanoseconds (experimental)
+    ///
+    /// Briefly takes over the TWI pins, drives SCL low, then
+    /// releases it and times how long it takes to read as high.
+    /// A long rise time indicates too weak pull-ups or too much
+    /// bus capacitance. The measurement includes the pin sampling
+    /// loop overhead, so it is only an indication.
+    ///
+    /// This must not be called during a transaction. The port C
+    /// registers are restored and the TWI hardware is
+    /// re-initialized afterwards. Returns `None` if no timer is
+    /// set, or if SCL never reads as high.
+    #[cfg(feature = "experimental-diagnostics")]
+    pub fn measure_rise_time(&self) -> Option<u32> {
+        let timer_ns = self.timer_ns?;
+        let saved = PortState::save();
+        write_register(TWCR, 0x00);
+        write_register(PORTC, unsafe { PORTC.read_volatile() } & !SCL);
+        drive_line_low(SCL);
+        delay_us(self.line_timing.hold_us);
+        write_register(DDRC, unsafe { DDRC.read_volatile() } & !SCL);
+        let start = timer_ns();
+        let mut risen = false;
+        for _ in 0..RISE_TIME_MAX_SAMPLES {
+            if unsafe { PINC.read_volatile() } & SCL != 0 {
+                risen = true;
+                break;
+            }
+        }
+        let elapsed = timer_ns().wrapping_sub(start);
+        saved.restore();
+        self.init();
+        risen.then_some(elapsed)
+    }
+
+    /// Reads the status register and checks it against `accept`
+    ///
+    /// Returns the accepted status. Unknown status codes are
+    /// handled according to the configured [`UnknownStatusPolicy`].
+    fn expect_status(
+        &self,
+        context: &Context<'_>,
+        accept: impl Fn(TWSRStatus) -> bool,
+    ) -> Result<TWSRStatus, TWSRStatus> {
+        let mut status = read_status();
+        if let UnknownStatusPolicy::RetryRead(retries) = self.unknown_status_policy {
+            for _ in 0..retries {
+                if !matches!(status, TWSRStatus::Unknown(_)) {
+                    break;
+                }
+                status = read_status();
+            }
+        }
+        context.record(TraceEvent::Status(status));
+        match status {
+            x if accept(x) => Ok(x),
+            x @ TWSRStatus::Unknown(_)
+                if self.unknown_status_policy == UnknownStatusPolicy::Ignore =>
+            {
+                Ok(x)
+            }
+            x => Err(x),
+        }
+    }
+
+    /// Returns the maximum SCL frequency reachable with a CPU clock of `fcpu` Hz
+    ///
+    /// This is the frequency at the minimum TWBR value of
+    /// [`MIN_TWBR`] with a prescaler of 1, i.e.
+    /// `fcpu / (16 + 2 * MIN_TWBR)`; a larger prescaler only
+    /// lowers the frequency. At 16 MHz this is 444 444 Hz.
+    pub const fn max_frequency_hz(fcpu: u32) -> u32 {
+        fcpu / (16 + 2 * MIN_TWBR as u32)
+    }
+
+    /// Returns the byte last clocked in or out of the data register
+    ///
+    /// This only reads TWDR and never touches TWCR, so
+    /// it does not advance the bus.
+    pub fn peek_data(&self) -> u8 {
+        unsafe { TWDR.read_volatile() }
+    }
+
+    /// Returns the current decoded status of the hardware
+    ///
+    /// This only reads TWSR and never touches TWCR, so
+    /// it does not advance the bus.
+    pub fn peek_status(&self) -> TWSRStatus {
+        read_status()
+    }
+
+    /// Reserves the bus until the returned guard is dropped
+    ///
+    /// Global interrupts are disabled while the guard is held,
+    /// so no interrupt handler can start a transaction between
+    /// the transactions performed through the guard. Interrupt
+    /// latency grows by the whole time the guard is held, which
+    /// for several transactions at 100 kHz easily reaches
+    /// milliseconds: keep the guarded section short.
+    ///
+    /// The previous interrupt state is restored on drop,
+    /// so guards may be nested.
+    pub fn lock(&self) -> BusGuard<'_> {
+        let sreg = unsafe { SREG.read_volatile() };
+        write_register(SREG, sreg & !SREG_I);
+        BusGuard { twi: self, sreg }
+    }
+
+    /// Performs a [`write_read`] with the bus reserved, see [`lock`]
+    ///
+    /// No interrupt handler can use the bus between the write
+    /// and the read, which makes multi-step device commands
+    /// atomic. The reservation is released when this returns,
+    /// on success and on error alike. Interrupts stay disabled
+    /// for the whole transaction, which adds its full duration
+    /// to the interrupt latency.
+    ///
+    /// [`write_read`]: I2CBus::write_read
+    /// [`lock`]: Self::lock
+    pub fn locked_write_read(
+        &self,
+        address: impl TryInto<SevenBitAddress>,
+        write: &[u8],
+        read: &mut [u8],
+    ) -> Result<(), TransactionError<Self>> {
+        let guard = self.lock();
+        guard.write_read(address, write, read)
+    }
+
+    /// Returns the register block of the TWI hardware
+    pub const fn registers(&self) -> TwiRegisterBlock {
+        TWI_REGISTERS
+    }
+
+    /// Sets the SCL frequency of the initialized hardware
+    ///
+    /// The bit rate is computed for the current prescaler, see
+    /// [`set_prescaler`], and saturates to the frequencies
+    /// reachable with it. It is rounded so the frequency does
+    /// not exceed `freq_hz`, unless that is above the highest
+    /// reachable frequency.
+    ///
+    /// [`set_prescaler`]: Self::set_prescaler
+    pub fn set_frequency(&self, freq_hz: u32) {
+        let twbr = twbr_for_frequency(freq_hz, self.prescaler());
+        write_register(TWBR, clamp_twbr(twbr));
+    }
+
+    /// Returns the current SCL frequency of the hardware in Hz
+    pub fn frequency(&self) -> u32 {
+        let twbr = unsafe { TWBR.read_volatile() } as u32;
+        F_CPU / (16 + 2 * (twbr << (2 * self.prescaler())))
+    }
+
+    /// Sets the TWPS prescaler bits of the initialized hardware,
+    /// dividing the bit rate by `4^twps`
+    ///
+    /// Returns `false`, leaving the prescaler unchanged, if `twps`
+    /// is not in `0..=3`. Only the low two bits of TWSR are
+    /// written; the status bits are read-only and unaffected.
+    /// The bit rate register is not adjusted, so the frequency
+    /// changes accordingly. [`init`] resets the prescaler to 0.
+    ///
+    /// [`init`]: I2CBus::init
+    pub fn set_prescaler(&self, twps: u8) -> bool {
+        if twps > 0x03 {
+            return false;
+        }
+        let twsr = unsafe { TWSR.read_volatile() };
+        write_register(TWSR, replace_prescaler(twsr, twps));
+        true
+    }
+
+    /// Returns the TWPS prescaler bits of the hardware
+    pub fn prescaler(&self) -> u8 {
+        let twsr = unsafe { TWSR.read_volatile() };
+        twsr & 0x03
+    }
+
+    /// Runs `f` with the SCL frequency clamped to `max_freq`
+    ///
+    /// If the current frequency exceeds `max_freq`, the bit
+    /// rate and the prescaler are lowered for the duration of
+    /// `f` and restored afterwards. This lets slow devices
+    /// share a bus with fast ones.
+    ///
+    /// The lowest prescaler reaching `max_freq` is used. If
+    /// `max_freq` is below the lowest reachable frequency,
+    /// about 490 Hz, the bus runs at that frequency instead.
+    pub fn with_device_freq<R>(&self, max_freq: u32, f: impl FnOnce(&Self) -> R) -> R {
+        if self.frequency() <= max_freq {
+            return f(self);
+        }
+        let twbr = unsafe { TWBR.read_volatile() };
+        let twps = self.prescaler();
+        let (device_twps, device_twbr) = bit_rate_for_frequency(max_freq);
+        self.set_prescaler(device_twps);
+        write_register(TWBR, device_twbr);
+        let result = f(self);
+        self.set_prescaler(twps);
+        write_register(TWBR, twbr);
+        result
+    }
+
+    /// Finds the highest of the `candidates` frequencies at which
+    /// the slave at `address` communicates reliably
+    ///
+    /// Candidates are tried from the highest down. At each, the
+    /// slave is [probed] and one byte is read from it, and the
+    /// frequency is accepted once this succeeds
+    /// [`PROBE_RELIABLE_ATTEMPTS`] times in a row. Candidates
+    /// the bit rate cannot reach with the current prescaler, such
+    /// as those above [`max_frequency_hz`], are skipped instead of
+    /// being tried at a clamped rate. Returns `None` if no
+    /// candidate is reliable.
+    ///
+    /// The bus speed changes during the sweep and is restored
+    /// before returning, so the sweep must not overlap other
+    /// traffic.
+    ///
+    /// [probed]: I2CBus::probe
+    /// [`max_frequency_hz`]: Self::max_frequency_hz
+    pub fn probe_max_reliable_freq(
+        &self,
+        address: impl TryInto<SevenBitAddress>,
+        candidates: &[u32],
+    ) -> Option<u32> {
+        let address = address.try_into().ok()?;
+        let twbr = unsafe { TWBR.read_volatile() };
+        let twps = self.prescaler();
+        let mut below = u32::MAX;
+        let mut found = None;
+        while let Some(freq) = candidates
+            .iter()
+            .copied()
+            .filter(|&freq| freq < below && reaches_frequency(freq, twps))
+            .max()
+        {
+            below = freq;
+            self.set_frequency(freq);
+            let reliable = (0..PROBE_RELIABLE_ATTEMPTS)
+                .all(|_| self.probe(address) && self.read_from(address, &mut [0]).is_ok());
+            if reliable {
+                found = Some(freq);
+                break;
+            }
+        }
+        write_register(TWBR, twbr);
+        found
+    }
+
+    /// Aborts the current transaction
+    ///
+    /// Issues a stop condition and waits for the hardware
+    /// to release the bus, without completing any remaining
+    /// bytes of the transaction. This is safe to call after
+    /// any [`I2CBus`] operation has returned, including after
+    /// an error.
+    ///
+    /// There is no transaction handle to invalidate: the bus
+    /// is idle once this returns, and the next transaction
+    /// must begin with a fresh [`start_condition`].
+    ///
+    /// If the last byte read was acknowledged, the slave may
+    /// still be driving SDA and the stop condition can be held
+    /// off until it releases the line. The wait is bounded by
+    /// the configured timeout, like [`wait_idle`].
+    ///
+    /// [`start_condition`]: I2CBus::start_condition
+    /// [`wait_idle`]: Self::wait_idle
+    pub fn abort(&self) -> Result<(), TWIError> {
+        self.stop();
+        let context = self.context();
+        context.check(context.await_stop())
+    }
+
+    /// Ends a read whose bytes were all acknowledged, e.g. by
+    /// [`read_ack_all`], with a stop condition
+    ///
+    /// A slave whose last byte was acknowledged keeps transmitting
+    /// and may hold SDA low. This clocks one dummy byte answered
+    /// with a NACK, which releases the slave, then issues a stop
+    /// condition. The dummy byte is discarded. The stop condition
+    /// is issued even if the dummy read fails.
+    ///
+    /// [`read_ack_all`]: I2CBus::read_ack_all
+    pub fn end_read(&self) -> Result<(), TWIError> {
+        let context = self.context();
+        let result = context.check(self.read_in(&context, &mut [0], true));
+        let stop = context.check(self.stop_condition_in(&context));
+        result.and(stop)
+    }
+
+    /// Creates an I2C stop condition on the bus without waiting
+    /// for it to complete
+    ///
+    /// The bus is not guaranteed to be idle on return. The next
+    /// start condition waits for the stop condition to complete,
+    /// and [`wait_idle`] waits for it explicitly, e.g. before
+    /// other code drives the bus lines.
+    ///
+    /// [`wait_idle`]: Self::wait_idle
+    pub fn stop_condition_nowait(&self) {
+        self.stop();
+    }
+
+    /// Waits for a pending stop condition to complete
+    ///
+    /// Returns immediately if no stop condition is pending.
+    pub fn wait_idle(&self) -> Result<(), TWIError> {
+        let context = self.context();
+        context.check(context.await_stop())
+    }
+
+    /// Checks the health of the idle bus
+    ///
+    /// Samples SCL and SDA, which should both be released
+    /// (pulled high) while the bus is idle. This must not be
+    /// called during a transaction.
+    pub fn check_bus_health(&self) -> Result<(), BusFault> {
+        let pins = unsafe { PINC.read_volatile() };
+        if pins & SCL == 0 {
+            Err(BusFault::SclHeldLow)
+        } else if pins & SDA == 0 {
+            Err(BusFault::SdaHeldLow)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Watches the idle bus for activity of another master
+    ///
+    /// Samples SCL and SDA `sample_iters` times and returns `true`
+    /// as soon as either line changes, since this master is not
+    /// driving the bus. This must not be called during a
+    /// transaction.
+    ///
+    /// This is a heuristic: another master that stays quiet during
+    /// the window goes undetected, and a noisy line can be mistaken
+    /// for one. It helps decide whether to expect arbitration loss,
+    /// not to guarantee sole ownership of the bus.
+    pub fn detect_other_master(&self, sample_iters: u32) -> bool {
+        let initial = unsafe { PINC.read_volatile() } & (SCL | SDA);
+        (0..sample_iters).any(|_| unsafe { PINC.read_volatile() } & (SCL | SDA) != initial)
+    }
+
+    /// Recovers a bus held by a slave stuck mid-transmission
+    ///
+    /// Temporarily takes over the TWI pins, clocks SCL up to
+    /// nine times until the slave releases SDA, then generates
+    /// a stop condition and re-initializes the TWI hardware.
+    ///
+    /// The port C output and direction bits are restored before
+    /// the hardware is re-initialized, so pull-ups enabled on the
+    /// TWI pins survive the recovery.
+    ///
+    /// If either line is still held low afterwards, the bus is
+    /// permanently stuck and [`BusFault::Unrecoverable`] is
+    /// returned; power-cycling the bus is then the only remedy.
+    pub fn recover_bus(&self) -> Result<(), BusFault> {
+        let saved = PortState::save();
+        write_register(TWCR, 0x00);
+        write_register(PORTC, unsafe { PORTC.read_volatile() } & !(SCL | SDA));
+        let timing = self.line_timing;
+        release_line(SCL | SDA, timing);
+        delay_us(timing.hold_us);
+        for _ in 0..9 {
+            if unsafe { PINC.read_volatile() } & SDA != 0 {
+                break;
+            }
+            drive_line_low(SCL);
+            delay_us(timing.hold_us + timing.setup_us);
+            release_line(SCL, timing);
+            delay_us(timing.hold_us);
+        }
+        delay_us(timing.start_setup_us);
+        drive_line_low(SDA);
+        delay_us(timing.setup_us);
+        release_line(SDA, timing);
+        delay_us(timing.hold_us);
+        let result = self.check_bus_health().map_err(|_| BusFault::Unrecoverable);
+        saved.restore();
+        self.init();
+        result
+    }
+
+    /// Releases a bus left mid-transfer by a reset of the MCU
+    ///
+    /// A watchdog or brownout reset can interrupt a transaction
+    /// and leave a slave mid-transmission, which then corrupts
+    /// the first transaction after boot. This recovers the bus
+    /// as [`recover_bus`] does, flushing the slave with clock
+    /// pulses and ending with a clean stop condition.
+    ///
+    /// It is meant to be called early in `main`, before
+    /// [`init`]. The hardware is left initialized, so calling
+    /// [`init`] afterwards is harmless.
+    ///
+    /// [`recover_bus`]: Self::recover_bus
+    /// [`init`]: I2CBus::init
+    pub fn recover_on_boot(&self) -> Result<(), BusFault> {
+        self.recover_bus()
+    }
+}
+
+/// Represents the kind of a failed [`TWI`] operation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TWIErrorKind {
+    /// The hardware reported an unexpected status
+    Status(TWSRStatus),
+    /// The transfer exceeds the configured maximum length
+    TransferTooLarge,
+    /// The hardware did not complete an operation in time
+    Timeout,
+    /// The byte at `index` of a read was answered with a NACK
+    /// where an ACK was expected
+    UnexpectedNack { index: usize },
+    /// The byte at `index` of a read was answered with an ACK
+    /// where a NACK was expected
+    ///
+    /// The slave then keeps transmitting: the bus should
+    /// be released with [`TWI::abort`].
+    UnexpectedAck { index: usize },
+}
+
+impl TWIErrorKind {
+    /// Returns whether the error may not recur when retrying
+    ///
+    /// NACKs, arbitration losses and timeouts are transient, as
+    /// they are caused by a busy or recovering device, or by
+    /// another master. Bus errors, unknown statuses and oversized
+    /// transfers are not.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Self::Timeout
+                | Self::Status(
+                    TWSRStatus::WriteHeaderTransmittedNackReceived
+                        | TWSRStatus::ReadHeaderTransmittedNackReceived
+                        | TWSRStatus::DataTransmittedNackReceived
+                        | TWSRStatus::ArbitrationLost
+                )
+        )
+    }
+}
+
+/// Represents an error of a [`TWI`] operation
+///
+/// With the `register-snapshot` feature, the error also carries
+/// the registers immediately after the failed operation, see
+/// [`RegisterSnapshot`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TWIError {
+    pub kind: TWIErrorKind,
+    /// The registers immediately after the failed operation,
+    /// before the stop condition or any other recovery
+    ///
+    /// Errors raised before any bus activity, such as
+    /// [`TWIErrorKind::TransferTooLarge`], carry none.
+    #[cfg(feature = "register-snapshot")]
+    pub snapshot: Option<RegisterSnapshot>,
+}
+
+impl TWIError {
+    /// Returns whether the error may not recur when retrying,
+    /// see [`TWIErrorKind::is_transient`]
+    pub fn is_transient(&self) -> bool {
+        self.kind.is_transient()
+    }
+}
+
+impl TransactionError<TWI> {
+    /// Returns the [`TWIError`] of the failed step, or `None` if
+    /// the transaction failed before any bus activity
+    pub fn cause(&self) -> Option<TWIError> {
+        match self {
+            Self::StartCondition(x)
+            | Self::StopCondition(x)
+            | Self::SendHeader(x)
+            | Self::Send(x)
+            | Self::Read(x) => Some(*x),
+            Self::InvalidAddress => None,
+        }
+    }
+}
+
+impl From<TWIErrorKind> for TWIError {
+    fn from(kind: TWIErrorKind) -> Self {
+        Self {
+            kind,
+            #[cfg(feature = "register-snapshot")]
+            snapshot: None,
+        }
+    }
+}
+
+impl From<TWSRStatus> for TWIError {
+    fn from(status: TWSRStatus) -> Self {
+        TWIErrorKind::Status(status).into()
+    }
+}
+
+/// Represents the values of the TWI registers at one point in time
+///
+/// Every [`TWIError`] of a failed bus operation carries the
+/// registers captured immediately after that operation, before
+/// the stop condition issued by the helpers or any other
+/// recovery. Capturing later, once the error is returned, shows
+/// the registers after that recovery instead.
+#[cfg(feature = "register-snapshot")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RegisterSnapshot {
+    pub twcr: u8,
+    pub twdr: u8,
+    pub twsr: u8,
+}
+
+#[cfg(feature = "register-snapshot")]
+impl RegisterSnapshot {
+    /// Reads the current values of the TWI registers
+    ///
+    /// Reading the registers has no side effect on the hardware.
+    pub fn capture() -> Self {
+        unsafe {
+            Self {
+                twcr: TWCR.read_volatile(),
+                twdr: TWDR.read_volatile(),
+                twsr: TWSR.read_volatile(),
+            }
         }
     }
 }
 
+/// Represents a failed transaction along with the registers
+/// at the failed operation, see [`TWI::write_read_snapshot`]
+#[cfg(feature = "register-snapshot")]
+#[derive(Debug)]
+pub struct SnapshotError {
+    pub error: TransactionError<TWI>,
+    pub snapshot: RegisterSnapshot,
+}
+
+/// Represents the registers and control masks of a TWI hardware
+///
+/// This lets tooling reach the registers of any backend uniformly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TwiRegisterBlock {
+    pub twbr: *mut u8,
+    pub twsr: *mut u8,
+    pub twar: *mut u8,
+    pub twdr: *mut u8,
+    pub twcr: *mut u8,
+    pub twint: u8,
+    pub twea: u8,
+    pub twsta: u8,
+    pub twsto: u8,
+    pub twen: u8,
+    pub twie: u8,
+    pub tws_mask: u8,
+}
+
+/// The register block of the ATMega328P TWI hardware
+pub const TWI_REGISTERS: TwiRegisterBlock = TwiRegisterBlock {
+    twbr: TWBR,
+    twsr: TWSR,
+    twar: TWAR,
+    twdr: TWDR,
+    twcr: TWCR,
+    twint: TWINT,
+    twea: TWEA,
+    twsta: TWSTA,
+    twsto: TWSTO,
+    twen: TWEN,
+    twie: TWIE,
+    tws_mask: TWS_MASK,
+};
+
+/// Represents an observer of the outcome of every transaction
+/// performed by the [`I2CBus`] helpers of a [`TWI`]
+///
+/// This is a lighter alternative to tracing for logging,
+/// counting failures or feeding a watchdog in one place.
+/// Transactions performed through a view, such as
+/// [`TWI::timed`], are observed too.
+#[cfg(feature = "transaction-observer")]
+pub trait TransactionObserver {
+    /// Called once a transaction completed, with its result
+    fn after(&self, result: &Result<(), TransactionError<TWI>>);
+}
+
+/// Represents a reservation of the bus, see [`TWI::lock`]
+pub struct BusGuard<'a> {
+    twi: &'a TWI,
+    sreg: u8,
+}
+
+impl core::ops::Deref for BusGuard<'_> {
+    type Target = TWI;
+
+    fn deref(&self) -> &TWI {
+        self.twi
+    }
+}
+
+impl Drop for BusGuard<'_> {
+    fn drop(&mut self) {
+        write_register(SREG, unsafe { SREG.read_volatile() } | (self.sreg & SREG_I));
+    }
+}
+
+/// Represents how a [`TWI`] handles a TWSR status code
+/// that does not match any [`TWSRStatus`] variant
+///
+/// Such codes are not produced by working hardware, but a
+/// glitch on the status read can produce one.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum UnknownStatusPolicy {
+    /// Fails the operation with [`TWSRStatus::Unknown`]
+    ///
+    /// This is the default, and the only policy that
+    /// never masks a genuine fault.
+    #[default]
+    Abort,
+    /// Re-reads TWSR up to the given number of times
+    /// before failing with [`TWSRStatus::Unknown`]
+    ///
+    /// This recovers from a glitched read at the cost of a
+    /// few extra register reads, but cannot recover from a
+    /// glitch in the bus state itself.
+    RetryRead(u8),
+    /// Treats the operation as successful
+    ///
+    /// This keeps a transaction going through a glitched read,
+    /// but may hide a failed operation and corrupt the data
+    /// of the rest of the transaction.
+    Ignore,
+}
+
+/// Represents the timing used when the bus lines are manipulated directly
+///
+/// All values are in microseconds. These only apply to the
+/// bit-level line manipulation performed by helpers such as
+/// [`TWI::recover_bus`]; the TWI hardware generates its own
+/// timing for regular transfers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineTiming {
+    /// The time a line is held stable before the clock edge that samples it
+    pub setup_us: u32,
+    /// The time a line is held after a clock edge
+    pub hold_us: u32,
+    /// The allowance for a released line to be raised by the pull-ups
+    pub rise_us: u32,
+    /// The time SCL is held high before a (repeated) start condition
+    pub start_setup_us: u32,
+}
+
+impl LineTiming {
+    /// Standard-mode (100 kHz) timing, rounded up to whole microseconds
+    ///
+    /// The start setup time is 4.7 µs.
+    pub const STANDARD_MODE: Self = Self {
+        setup_us: 1,
+        hold_us: 4,
+        rise_us: 1,
+        start_setup_us: 5,
+    };
+
+    /// Fast-mode (400 kHz) timing, rounded up to whole microseconds
+    ///
+    /// The start setup time is 0.6 µs.
+    pub const FAST_MODE: Self = Self {
+        setup_us: 1,
+        hold_us: 1,
+        rise_us: 1,
+        start_setup_us: 1,
+    };
+}
+
+impl Default for LineTiming {
+    fn default() -> Self {
+        Self::STANDARD_MODE
+    }
+}
+
+/// Represents a fault of the physical bus lines
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BusFault {
+    SclHeldLow,
+    SdaHeldLow,
+    Unrecoverable,
+}
+
 /// Represents status variants of the 2-wire status register
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -31,8 +899,23 @@ pub enum TWSRStatus {
     DataReceivedAckTransmitted,
     DataReceivedNackTransmitted,
     ArbitrationLost,
+    OwnWriteHeaderReceivedAckTransmitted,
+    ArbitrationLostOwnWriteHeaderReceived,
+    GeneralCallReceivedAckTransmitted,
+    ArbitrationLostGeneralCallReceived,
+    SlaveDataReceivedAckTransmitted,
+    SlaveDataReceivedNackTransmitted,
+    GeneralCallDataReceivedAckTransmitted,
+    GeneralCallDataReceivedNackTransmitted,
+    StopOrRepeatedStartReceived,
+    OwnReadHeaderReceivedAckTransmitted,
+    ArbitrationLostOwnReadHeaderReceived,
+    SlaveDataTransmittedAckReceived,
+    SlaveDataTransmittedNackReceived,
+    LastSlaveDataTransmittedAckReceived,
     NoInformation,
     BusError,
+    Unknown(u8),
 }
 
 impl TWSRStatus {
@@ -40,7 +923,8 @@ impl TWSRStatus {
     ///
     /// If `value` is a valid TWSR status value, a `Some`
     /// value containing [`Self`] is returned.
-    /// Otherwise, `None` is returned.
+    /// Otherwise, `None` is returned; [`read_status`]
+    /// reports such values as [`Self::Unknown`].
     ///
     /// [`Self`]: Self
     pub fn from_byte(value: u8) -> Option<Self> {
@@ -57,6 +941,20 @@ impl TWSRStatus {
             0x48 => Some(Self::ReadHeaderTransmittedNackReceived),
             0x50 => Some(Self::DataReceivedAckTransmitted),
             0x58 => Some(Self::DataReceivedNackTransmitted),
+            0x60 => Some(Self::OwnWriteHeaderReceivedAckTransmitted),
+            0x68 => Some(Self::ArbitrationLostOwnWriteHeaderReceived),
+            0x70 => Some(Self::GeneralCallReceivedAckTransmitted),
+            0x78 => Some(Self::ArbitrationLostGeneralCallReceived),
+            0x80 => Some(Self::SlaveDataReceivedAckTransmitted),
+            0x88 => Some(Self::SlaveDataReceivedNackTransmitted),
+            0x90 => Some(Self::GeneralCallDataReceivedAckTransmitted),
+            0x98 => Some(Self::GeneralCallDataReceivedNackTransmitted),
+            0xA0 => Some(Self::StopOrRepeatedStartReceived),
+            0xA8 => Some(Self::OwnReadHeaderReceivedAckTransmitted),
+            0xB0 => Some(Self::ArbitrationLostOwnReadHeaderReceived),
+            0xB8 => Some(Self::SlaveDataTransmittedAckReceived),
+            0xC0 => Some(Self::SlaveDataTransmittedNackReceived),
+            0xC8 => Some(Self::LastSlaveDataTransmittedAckReceived),
             0xF8 => Some(Self::NoInformation),
             _ => None,
         }
@@ -69,12 +967,56 @@ pub const TWBR: *mut u8 = 0x00B8 as *mut u8;
 /// The address of the 2-wire status register
 pub const TWSR: *mut u8 = 0x00B9 as *mut u8;
 
+/// The mask for the status bits of the 2-wire status register
+pub const TWS_MASK: u8 = 0xF8;
+
+/// The address of the 2-wire (slave) address register
+pub const TWAR: *mut u8 = 0x00BA as *mut u8;
+
 /// The address of the 2-wire data register
 pub const TWDR: *mut u8 = 0x00BB as *mut u8;
 
 /// The address of the 2-wire control register
 pub const TWCR: *mut u8 = 0x00BC as *mut u8;
 
+/// The mask for setting the TWAR general call recognition enable bit
+pub const TWGCE: u8 = 0x01;
+
+/// The address of the port C input pins register
+pub const PINC: *mut u8 = 0x0026 as *mut u8;
+
+/// The address of the port C data direction register
+pub const DDRC: *mut u8 = 0x0027 as *mut u8;
+
+/// The address of the port C data register
+pub const PORTC: *mut u8 = 0x0028 as *mut u8;
+
+/// The address of the status register
+pub const SREG: *mut u8 = 0x005F as *mut u8;
+
+/// The mask for the SREG global interrupt enable bit
+pub const SREG_I: u8 = 0x80;
+
+/// The mask for the port C SDA pin
+pub const SDA: u8 = 0x10;
+
+/// The mask for the port C SCL pin
+pub const SCL: u8 = 0x20;
+
+/// The maximum number of pin samples taken by [`TWI::measure_rise_time`]
+#[cfg(feature = "experimental-diagnostics")]
+const RISE_TIME_MAX_SAMPLES: u16 = 10_000;
+
+/// The high-speed mode master code, without the master-specific bits
+pub const HS_MASTER_CODE: u8 = 0x08;
+
+/// The number of consecutive successful test transactions
+/// required by [`TWI::probe_max_reliable_freq`]
+pub const PROBE_RELIABLE_ATTEMPTS: u8 = 8;
+
+/// The minimum TWBR value for reliable master operation
+pub const MIN_TWBR: u8 = 10;
+
 /// The mask for setting the TWCR interrupt bit
 pub const TWINT: u8 = 0x80;
 
@@ -90,6 +1032,9 @@ pub const TWSTO: u8 = 0x10;
 /// The mask for setting the TWCR enable bit
 pub const TWEN: u8 = 0x04;
 
+/// The mask for setting the TWCR interrupt enable bit
+pub const TWIE: u8 = 0x01;
+
 /// Awaits TWI hardware availability
 ///
 /// Repeatedly polls the TWCR until it signifies that
@@ -101,88 +1046,902 @@ pub fn await_hardware() {
     }
 }
 
-impl I2CBus for TWI {
-    type StartConditionError = TWSRStatus;
-    type StopCondidionError = ();
-    type SendHeaderError = TWSRStatus;
-    type SendError = TWSRStatus;
-    type ReadError = TWSRStatus;
+/// Reads and decodes the 2-wire status register
+///
+/// The prescaler bits are masked off. Values that are not
+/// valid TWSR status values are returned as [`TWSRStatus::Unknown`].
+pub fn read_status() -> TWSRStatus {
+    decode_status(unsafe { TWSR.read_volatile() })
+}
 
-    fn init(&self) {
-        unsafe {
-            TWSR.write_volatile(0x00);
-            TWBR.write_volatile(self.twbr);
-            TWCR.write_volatile(TWEN);
+/// Decodes the status bits of the raw TWSR value `twsr`
+fn decode_status(twsr: u8) -> TWSRStatus {
+    let value = twsr & TWS_MASK;
+    TWSRStatus::from_byte(value).unwrap_or(TWSRStatus::Unknown(value))
+}
+
+/// Returns the raw TWSR value `twsr` with its TWPS prescaler
+/// bits replaced by `twps`
+const fn replace_prescaler(twsr: u8, twps: u8) -> u8 {
+    (twsr & !0x03) | twps
+}
+
+/// Decodes a captured sequence of raw TWSR values
+///
+/// The prescaler bits of each value are masked off before
+/// decoding with [`TWSRStatus::from_byte`], so values can be
+/// captured straight from the register.
+#[cfg(feature = "decode")]
+pub fn decode_sequence(raw: &[u8]) -> impl Iterator<Item = Option<TWSRStatus>> + '_ {
+    raw.iter()
+        .map(|value| TWSRStatus::from_byte(value & TWS_MASK))
+}
+
+/// Awaits completion of a stop condition
+///
+/// Repeatedly polls the TWCR until the hardware clears
+/// the stop condition bit, signifying that the stop
+/// condition has been transmitted, then returns.
+pub fn await_stop() {
+    while unsafe { TWCR.read_volatile() } & TWSTO != 0 {
+        continue;
+    }
+}
+
+/// Computes the TWBR value for an SCL frequency of `freq_hz`
+/// with the TWPS prescaler bits `twps`
+///
+/// The value is rounded up, so the frequency does not exceed
+/// `freq_hz`. It is not limited to the range of TWBR, see
+/// [`clamp_twbr`]; a frequency of 0 gives `u32::MAX`.
+const fn twbr_for_frequency(freq_hz: u32, twps: u8) -> u32 {
+    if freq_hz == 0 {
+        return u32::MAX;
+    }
+    F_CPU
+        .div_ceil(freq_hz)
+        .saturating_sub(16)
+        .div_ceil(2 << (2 * twps))
+}
+
+/// Returns the TWCR value clocking in byte `index` of a read
+/// of `count` bytes
+///
+/// Every byte is answered with an ACK, except the last one,
+/// which is answered with a NACK if `end_with_nack` is set.
+const fn read_control(index: usize, count: usize, end_with_nack: bool) -> u8 {
+    let ack = !(end_with_nack && index + 1 == count);
+    TWINT | TWEN | if ack { TWEA } else { 0 }
+}
+
+/// Returns whether a TWBR value in `MIN_TWBR..=255` reaches
+/// an SCL frequency of `freq_hz` with the prescaler bits `twps`
+const fn reaches_frequency(freq_hz: u32, twps: u8) -> bool {
+    let twbr = twbr_for_frequency(freq_hz, twps);
+    twbr >= MIN_TWBR as u32 && twbr <= u8::MAX as u32
+}
+
+/// Saturates a TWBR value to `MIN_TWBR..=255`
+const fn clamp_twbr(twbr: u32) -> u8 {
+    if twbr > u8::MAX as u32 {
+        u8::MAX
+    } else if twbr < MIN_TWBR as u32 {
+        MIN_TWBR
+    } else {
+        twbr as u8
+    }
+}
+
+/// Returns the lowest TWPS prescaler bits reaching an SCL
+/// frequency of `freq_hz`, with the TWBR value for them
+///
+/// Frequencies too low for the largest prescaler saturate
+/// to the lowest reachable frequency.
+const fn bit_rate_for_frequency(freq_hz: u32) -> (u8, u8) {
+    let mut twps = 0;
+    while twps < 3 && twbr_for_frequency(freq_hz, twps) > u8::MAX as u32 {
+        twps += 1;
+    }
+    (twps, clamp_twbr(twbr_for_frequency(freq_hz, twps)))
+}
+
+/// Busy-waits for approximately `us` microseconds
+///
+/// The delay is calibrated against [`F_CPU`]
+/// assuming four cycles per loop iteration. Delays too long
+/// to count saturate to the longest one.
+pub fn delay_us(us: u32) {
+    for i in 0..us.saturating_mul(F_CPU / 4_000_000) {
+        core::hint::black_box(i);
+    }
+}
+
+/// Writes `value` to the register at `register`
+///
+/// Every register write performed by this module goes
+/// through here. With the `register-trace` feature, the
+/// callback installed with [`set_register_trace`] is
+/// invoked with the register name and value first.
+fn write_register(register: *mut u8, value: u8) {
+    #[cfg(feature = "register-trace")]
+    if let Some(callback) = unsafe { REGISTER_TRACE } {
+        callback(register_name(register), value);
+    }
+    unsafe {
+        register.write_volatile(value);
+    }
+}
+
+/// The callback invoked on every register write
+#[cfg(feature = "register-trace")]
+static mut REGISTER_TRACE: Option<fn(&'static str, u8)> = None;
+
+/// Installs a callback invoked on every register write
+///
+/// The callback receives the register name
+/// (e.g. `"TWCR"`) and the value about to be written.
+///
+/// # Safety
+///
+/// This must not be called while a register write may be
+/// in progress, e.g. from an interrupt handler that can
+/// preempt a transaction.
+#[cfg(feature = "register-trace")]
+pub unsafe fn set_register_trace(callback: fn(&'static str, u8)) {
+    REGISTER_TRACE = Some(callback);
+}
+
+/// Returns the name of the register at `register`
+#[cfg(feature = "register-trace")]
+fn register_name(register: *mut u8) -> &'static str {
+    match register as usize {
+        x if x == TWBR as usize => "TWBR",
+        x if x == TWSR as usize => "TWSR",
+        x if x == TWAR as usize => "TWAR",
+        x if x == TWDR as usize => "TWDR",
+        x if x == TWCR as usize => "TWCR",
+        x if x == DDRC as usize => "DDRC",
+        x if x == PORTC as usize => "PORTC",
+        x if x == SREG as usize => "SREG",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Releases the port C lines in `mask`, letting the pull-ups raise them
+///
+/// Waits for the rise allowance of `timing` before returning.
+fn release_line(mask: u8, timing: LineTiming) {
+    write_register(DDRC, unsafe { DDRC.read_volatile() } & !mask);
+    delay_us(timing.rise_us);
+}
+
+/// Drives the port C lines in `mask` low
+fn drive_line_low(mask: u8) {
+    write_register(DDRC, unsafe { DDRC.read_volatile() } | mask);
+}
+
+/// Represents the port C registers saved before bit-banging the bus
+#[derive(Clone, Copy)]
+struct PortState {
+    /// The saved output register
+    port: u8,
+    /// The saved data direction register
+    ddr: u8,
+}
+
+impl PortState {
+    /// Reads the current port C registers
+    fn save() -> Self {
+        Self {
+            port: unsafe { PORTC.read_volatile() },
+            ddr: unsafe { DDRC.read_volatile() },
         }
     }
 
-    fn start_condition(&self) -> Result<(), Self::StartConditionError> {
-        unsafe {
-            TWCR.write_volatile(TWINT | TWSTA | TWEN);
+    /// Writes the saved registers back
+    ///
+    /// The output register is restored first, so a pin saved as
+    /// a high output is never driven low on the way back.
+    fn restore(self) {
+        write_register(PORTC, self.port);
+        write_register(DDRC, self.ddr);
+    }
+}
+
+/// Represents the per-call state of bus operations
+struct Context<'a> {
+    /// The number of polls allowed per operation, or `None` to wait forever
+    per_operation: Option<u32>,
+    /// The number of polls left for all operations, or `None` for no limit
+    remaining: Cell<Option<u32>>,
+    /// The number of polls taken by all operations so far
+    used: Cell<u32>,
+    /// The trace recording the operations, if any
+    trace: Option<&'a RefCell<dyn Trace + 'a>>,
+}
+
+impl Context<'_> {
+    /// Records `event` in the trace, if any
+    fn record(&self, event: TraceEvent) {
+        if let Some(trace) = self.trace {
+            trace.borrow_mut().record(event);
         }
-        await_hardware();
-        match TWSRStatus::from_byte(unsafe { TWSR.read_volatile() }).unwrap() {
-            TWSRStatus::StartTransmitted => Ok(()),
-            x => Err(x),
+    }
+
+    /// Polls until `done` returns `true`, within the timeout
+    fn poll(&self, done: impl Fn() -> bool) -> Result<(), TWIError> {
+        let mut polls = 0;
+        while !done() {
+            polls += 1;
+            self.used.set(self.used.get().saturating_add(1));
+            if self.per_operation.is_some_and(|max| polls > max) {
+                return Err(TWIErrorKind::Timeout.into());
+            }
+            match self.remaining.get() {
+                Some(0) => return Err(TWIErrorKind::Timeout.into()),
+                Some(remaining) => self.remaining.set(Some(remaining - 1)),
+                None => (),
+            }
         }
+        Ok(())
     }
 
-    fn stop_condition(&self) -> Result<(), ()> {
-        unsafe {
-            TWCR.write_volatile(TWINT | TWSTO | TWEN);
+    /// Awaits TWI hardware availability, within the timeout
+    fn await_hardware(&self) -> Result<(), TWIError> {
+        self.poll(|| unsafe { TWCR.read_volatile() } & TWINT != 0)
+    }
+
+    /// Awaits completion of a stop condition, within the timeout
+    fn await_stop(&self) -> Result<(), TWIError> {
+        self.poll(|| unsafe { TWCR.read_volatile() } & TWSTO == 0)
+    }
+
+    /// Captures the registers into the error of `result`, if any
+    ///
+    /// This must be called as soon as the failed operation returns.
+    /// Errors raised before any bus activity are left without a
+    /// snapshot.
+    fn check<T>(&self, result: Result<T, TWIError>) -> Result<T, TWIError> {
+        #[cfg(feature = "register-snapshot")]
+        let result = result.map_err(|mut error| {
+            if error.kind != TWIErrorKind::TransferTooLarge && error.snapshot.is_none() {
+                error.snapshot = Some(RegisterSnapshot::capture());
+            }
+            error
+        });
+        result
+    }
+}
+
+impl TWI {
+    /// Returns a view of the bus whose operations each wait
+    /// at most `iterations` polls for the hardware
+    ///
+    /// This overrides the timeout configured with
+    /// [`with_timeout`] for the operations performed
+    /// through the view.
+    ///
+    /// [`with_timeout`]: Self::with_timeout
+    pub const fn timed(&self, iterations: u32) -> TimedTWI<'_> {
+        TimedTWI {
+            twi: self,
+            context: Context {
+                per_operation: Some(iterations),
+                ..self.context()
+            },
         }
-        await_hardware();
+    }
+
+    /// Reads consecutive bytes starting at `register` of the slave
+    /// at `address`, with each operation waiting at most
+    /// `iterations` polls for the hardware
+    ///
+    /// This lets known-slow registers be read patiently while
+    /// keeping the configured timeout tight for other traffic.
+    pub fn read_register_timeout(
+        &self,
+        address: impl TryInto<SevenBitAddress>,
+        register: u8,
+        data: &mut [u8],
+        iterations: u32,
+    ) -> Result<(), TransactionError<Self>> {
+        self.timed(iterations)
+            .read_register(address, register, data)
+            .map_err(Into::into)
+    }
+
+    /// Performs a [`write_read`], recording every byte sent and
+    /// received and every status decoded into `trace`
+    ///
+    /// `trace` is cleared first. Once it is full, further events
+    /// are dropped, which [`TraceBuffer::overflowed`] reports.
+    ///
+    /// [`write_read`]: I2CBus::write_read
+    pub fn write_read_traced<const N: usize>(
+        &self,
+        address: impl TryInto<SevenBitAddress>,
+        write: &[u8],
+        read: &mut [u8],
+        trace: &mut TraceBuffer<N>,
+    ) -> Result<(), TransactionError<Self>> {
+        trace.clear();
+        let trace = RefCell::new(trace);
+        let view = TimedTWI {
+            twi: self,
+            context: Context {
+                trace: Some(&trace),
+                ..self.context()
+            },
+        };
+        view.write_read(address, write, read).map_err(Into::into)
+    }
+
+    /// Runs `f`, retrying it with exponential backoff while it fails
+    /// with a transient error
+    ///
+    /// `f` is retried at most `max_retries` times, busy-waiting
+    /// `base_delay_us` microseconds before the first retry and
+    /// twice as long before each following one. Errors that are
+    /// not [transient] are returned immediately.
+    ///
+    /// [transient]: TWIError::is_transient
+    pub fn retry<R>(
+        &self,
+        max_retries: u8,
+        base_delay_us: u32,
+        mut f: impl FnMut(&Self) -> Result<R, TransactionError<Self>>,
+    ) -> Result<R, TransactionError<Self>> {
+        let mut delay = base_delay_us;
+        let mut retries = 0;
+        loop {
+            match f(self) {
+                Err(error)
+                    if retries < max_retries
+                        && error.cause().is_some_and(|cause| cause.is_transient()) =>
+                {
+                    delay_us(delay);
+                    delay = delay.saturating_mul(2);
+                    retries += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Performs a [`write_read`], retrying it with exponential
+    /// backoff on NACKs and timeouts, see [`retry`]
+    ///
+    /// This gives a flaky device time to recover between attempts,
+    /// while bus errors fail immediately.
+    ///
+    /// [`write_read`]: I2CBus::write_read
+    /// [`retry`]: Self::retry
+    pub fn write_read_backoff(
+        &self,
+        address: impl TryInto<SevenBitAddress>,
+        write: &[u8],
+        read: &mut [u8],
+        max_retries: u8,
+        base_delay_us: u32,
+    ) -> Result<(), TransactionError<Self>> {
+        let address = crate::seven_bit_address::<Self>(address)?;
+        self.retry(max_retries, base_delay_us, |twi| {
+            twi.write_read(address, write, read)
+        })
+    }
+
+    /// Sends the high-speed mode preamble
+    ///
+    /// Issues a start condition, sends the master code
+    /// `00001xxx` with the low three bits taken from
+    /// `master_code`, then issues a repeated start. No device
+    /// acknowledges a master code. On success, the bus is ready
+    /// for the header of the high-speed transaction.
+    ///
+    /// The ATMega328P cannot clock the bus faster than
+    /// [`max_frequency_hz`], so on this backend the transaction
+    /// following the preamble still runs at the configured
+    /// frequency; only the preamble itself is sent, for devices
+    /// that accept it gracefully.
+    ///
+    /// On error, the bus should be released with [`abort`].
+    ///
+    /// [`max_frequency_hz`]: Self::max_frequency_hz
+    /// [`abort`]: Self::abort
+    pub fn hs_mode_preamble(&self, master_code: u8) -> Result<(), TWIError> {
+        let context = self.context();
+        self.start_condition_in(&context)?;
+        let code = HS_MASTER_CODE | (master_code & 0x07);
+        write_register(TWDR, code);
+        write_register(TWCR, TWINT | TWEN);
+        context.record(TraceEvent::Sent(code));
+        context.await_hardware()?;
+        self.expect_status(&context, |status| {
+            matches!(
+                status,
+                TWSRStatus::WriteHeaderTransmittedNackReceived
+                    | TWSRStatus::ReadHeaderTransmittedNackReceived
+            )
+        })?;
+        self.start_condition_in(&context)
+    }
+
+    /// Performs a [`write_read`] within a total budget of
+    /// `total_iterations` polls for the hardware
+    ///
+    /// The budget is shared by every wait of the transaction, so
+    /// it bounds the worst case duration of the whole transaction
+    /// however the time is distributed across its bytes, e.g. when
+    /// a device stretches the clock on many of them. The configured
+    /// per-operation timeout still applies to each wait.
+    ///
+    /// [`write_read`]: I2CBus::write_read
+    pub fn write_read_deadline(
+        &self,
+        address: impl TryInto<SevenBitAddress>,
+        write: &[u8],
+        read: &mut [u8],
+        total_iterations: u32,
+    ) -> Result<(), TransactionError<Self>> {
+        let view = TimedTWI {
+            twi: self,
+            context: Context {
+                remaining: Cell::new(Some(total_iterations)),
+                ..self.context()
+            },
+        };
+        view.write_read(address, write, read).map_err(Into::into)
+    }
+
+    /// Reads consecutive bytes starting at `register` of the slave
+    /// at `address`, returning the number of polls the transaction
+    /// waited for the hardware
+    ///
+    /// The poll count is a proxy for the time the transaction took,
+    /// which makes it possible to spot slow or marginal devices.
+    pub fn read_register_profiled(
+        &self,
+        address: impl TryInto<SevenBitAddress>,
+        register: u8,
+        data: &mut [u8],
+    ) -> Result<u32, TransactionError<Self>> {
+        let view = TimedTWI {
+            twi: self,
+            context: self.context(),
+        };
+        view.read_register(address, register, data)?;
+        Ok(view.context.used.get())
+    }
+
+    /// Performs a [`write_read`], reporting the registers at the
+    /// failed operation next to the error
+    ///
+    /// The snapshot is the one carried by the [`TWIError`] of the
+    /// failed step, which reflects the registers immediately after
+    /// the failing operation, before the stop condition issued by
+    /// [`write_read`] or any other recovery. If the transaction
+    /// failed before any bus activity, the registers are captured
+    /// on return instead.
+    ///
+    /// [`write_read`]: I2CBus::write_read
+    #[cfg(feature = "register-snapshot")]
+    pub fn write_read_snapshot(
+        &self,
+        address: impl TryInto<SevenBitAddress>,
+        write: &[u8],
+        read: &mut [u8],
+    ) -> Result<(), SnapshotError> {
+        self.write_read(address, write, read)
+            .map_err(|error| SnapshotError {
+                snapshot: error
+                    .cause()
+                    .and_then(|cause| cause.snapshot)
+                    .unwrap_or_else(RegisterSnapshot::capture),
+                error,
+            })
+    }
+
+    /// Returns the context of operations performed directly on the bus
+    const fn context<'a>(&self) -> Context<'a> {
+        Context {
+            per_operation: self.timeout,
+            remaining: Cell::new(None),
+            used: Cell::new(0),
+            trace: None,
+        }
+    }
+
+    /// Creates a start condition, within `context`
+    ///
+    /// A pending stop condition, e.g. from [`stop_condition_nowait`],
+    /// completes first, since setting TWSTA while TWSTO is still set
+    /// would not create a clean start.
+    ///
+    /// [`stop_condition_nowait`]: Self::stop_condition_nowait
+    fn start_condition_in(&self, context: &Context<'_>) -> Result<(), TWIError> {
+        context.await_stop()?;
+        write_register(TWCR, TWINT | TWSTA | TWEN);
+        context.await_hardware()?;
+        self.expect_status(context, |status| {
+            matches!(
+                status,
+                TWSRStatus::StartTransmitted | TWSRStatus::RepeatedStartTransmitted
+            )
+        })?;
         Ok(())
     }
 
-    fn send_header(&self, address: u8, direction: Direction) -> Result<(), Self::SendHeaderError> {
-        let payload: u8 = (address << 1)
+    /// Creates a stop condition, within `context`
+    ///
+    /// The hardware does not set TWINT after a stop condition,
+    /// so this waits for TWSTO to clear instead.
+    fn stop_condition_in(&self, context: &Context<'_>) -> Result<(), TWIError> {
+        self.stop();
+        context.await_stop()
+    }
+
+    /// Sends an I2C header, within `context`
+    fn send_header_in(
+        &self,
+        context: &Context<'_>,
+        address: SevenBitAddress,
+        direction: Direction,
+    ) -> Result<(), TWIError> {
+        let payload: u8 = (address.get() << 1)
             | match direction {
                 Direction::Read => 1,
                 Direction::Write => 0,
             };
-        unsafe {
-            TWDR.write_volatile(payload);
-            TWCR.write_volatile(TWINT | TWEN);
-        }
-        await_hardware();
-        match TWSRStatus::from_byte(unsafe { TWSR.read_volatile() }).unwrap() {
-            TWSRStatus::ReadHeaderTransmittedAckReceived if direction == Direction::Read => Ok(()),
-            TWSRStatus::WriteHeaderTransmittedAckReceived if direction == Direction::Write => {
-                Ok(())
-            }
-            x => Err(x),
-        }
+        write_register(TWDR, payload);
+        write_register(TWCR, TWINT | TWEN);
+        context.record(TraceEvent::Sent(payload));
+        context.await_hardware()?;
+        self.expect_status(context, |status| match direction {
+            Direction::Read => status == TWSRStatus::ReadHeaderTransmittedAckReceived,
+            Direction::Write => status == TWSRStatus::WriteHeaderTransmittedAckReceived,
+        })?;
+        Ok(())
     }
 
-    fn send(&self, data: &[u8]) -> Result<(), Self::SendError> {
+    /// Sends data, within `context`
+    fn send_in(&self, context: &Context<'_>, data: &[u8]) -> Result<(), TWIError> {
+        self.check_transfer_len(data.len())?;
         for byte in data {
-            unsafe {
-                TWDR.write_volatile(*byte);
-                TWCR.write_volatile(TWINT | TWEN);
-            }
-            await_hardware();
-            match TWSRStatus::from_byte(unsafe { TWSR.read_volatile() }).unwrap() {
-                TWSRStatus::DataTransmittedAckReceived => (),
-                x => return Err(x),
-            }
+            write_register(TWDR, *byte);
+            write_register(TWCR, TWINT | TWEN);
+            context.record(TraceEvent::Sent(*byte));
+            context.await_hardware()?;
+            self.expect_status(context, |status| {
+                status == TWSRStatus::DataTransmittedAckReceived
+            })?;
         }
         Ok(())
     }
 
-    fn read(&self, data: &mut [u8], end_with_nack: bool) -> Result<(), Self::ReadError> {
-        for byte in data {
-            unsafe {
-                TWCR.write_volatile(TWINT | TWEN | if end_with_nack { TWEA } else { 0 });
-            }
-            await_hardware();
-            match TWSRStatus::from_byte(unsafe { TWSR.read_volatile() }).unwrap() {
-                TWSRStatus::DataReceivedAckTransmitted if end_with_nack => (),
-                TWSRStatus::DataReceivedNackTransmitted if !end_with_nack => (),
-                x => return Err(x),
+    /// Reads data, within `context`
+    ///
+    /// An empty `data` clocks no byte and leaves the bus untouched.
+    fn read_in(
+        &self,
+        context: &Context<'_>,
+        data: &mut [u8],
+        end_with_nack: bool,
+    ) -> Result<(), TWIError> {
+        self.check_transfer_len(data.len())?;
+        let count = data.len();
+        for (index, byte) in data.iter_mut().enumerate() {
+            // TWEA must be set up before the byte is clocked in,
+            // as it selects the ACK/NACK answering that byte.
+            let control = read_control(index, count, end_with_nack);
+            let ack = control & TWEA != 0;
+            write_register(TWCR, control);
+            context.await_hardware()?;
+            let status = self.expect_status(context, |status| {
+                matches!(
+                    status,
+                    TWSRStatus::DataReceivedAckTransmitted
+                        | TWSRStatus::DataReceivedNackTransmitted
+                )
+            })?;
+            match status {
+                TWSRStatus::DataReceivedNackTransmitted if ack => {
+                    return Err(TWIErrorKind::UnexpectedNack { index }.into())
+                }
+                TWSRStatus::DataReceivedAckTransmitted if !ack => {
+                    return Err(TWIErrorKind::UnexpectedAck { index }.into())
+                }
+                _ => (),
             }
             *byte = unsafe { TWDR.read_volatile() };
+            context.record(TraceEvent::Received(*byte));
         }
         Ok(())
     }
+
+    /// Reads data one byte at a time until `f` returns `false`,
+    /// within `context`
+    ///
+    /// At most `max_transfer_len` bytes are passed to `f`. If `f`
+    /// asks for more, the slave is released with the final dummy
+    /// byte and the read fails with [`TWIErrorKind::TransferTooLarge`].
+    fn read_with_in(
+        &self,
+        context: &Context<'_>,
+        mut f: impl FnMut(usize, u8) -> bool,
+    ) -> Result<usize, TWIError> {
+        let mut byte = [0];
+        let mut count = 0;
+        loop {
+            self.read_in(context, &mut byte, false)?;
+            let more = f(count, byte[0]);
+            count += 1;
+            if !more {
+                self.read_in(context, &mut byte, true)?;
+                return Ok(count);
+            }
+            if let Err(error) = self.check_transfer_len(count + 1) {
+                self.read_in(context, &mut byte, true)?;
+                return Err(error);
+            }
+        }
+    }
+}
+
+impl I2CBus for TWI {
+    type StartConditionError = TWIError;
+    type StopCondidionError = TWIError;
+    type SendHeaderError = TWIError;
+    type SendError = TWIError;
+    type ReadError = TWIError;
+
+    fn init(&self) {
+        write_register(TWSR, 0x00);
+        write_register(TWBR, self.twbr);
+        if let Some(address) = self.own_address {
+            write_register(TWAR, address.get() << 1);
+        }
+        write_register(TWCR, self.init_control());
+    }
+
+    fn start_condition(&self) -> Result<(), Self::StartConditionError> {
+        let context = self.context();
+        context.check(self.start_condition_in(&context))
+    }
+
+    fn stop_condition(&self) -> Result<(), Self::StopCondidionError> {
+        let context = self.context();
+        context.check(self.stop_condition_in(&context))
+    }
+
+    fn send_header(
+        &self,
+        address: impl Into<SevenBitAddress>,
+        direction: Direction,
+    ) -> Result<(), Self::SendHeaderError> {
+        let context = self.context();
+        context.check(self.send_header_in(&context, address.into(), direction))
+    }
+
+    fn send(&self, data: &[u8]) -> Result<(), Self::SendError> {
+        let context = self.context();
+        context.check(self.send_in(&context, data))
+    }
+
+    fn read(&self, data: &mut [u8], end_with_nack: bool) -> Result<(), Self::ReadError> {
+        let context = self.context();
+        context.check(self.read_in(&context, data, end_with_nack))
+    }
+
+    fn read_with(&self, f: impl FnMut(usize, u8) -> bool) -> Result<usize, Self::ReadError> {
+        let context = self.context();
+        context.check(self.read_with_in(&context, f))
+    }
+
+    #[cfg(feature = "transaction-observer")]
+    fn after_transaction(
+        &self,
+        result: Result<(), TransactionError<Self>>,
+    ) -> Result<(), TransactionError<Self>> {
+        if let Some(observer) = self.observer {
+            observer.after(&result);
+        }
+        result
+    }
+}
+
+/// Represents a view of a [`TWI`] with its own timeout, see [`TWI::timed`]
+pub struct TimedTWI<'a> {
+    twi: &'a TWI,
+    context: Context<'a>,
+}
+
+impl I2CBus for TimedTWI<'_> {
+    type StartConditionError = TWIError;
+    type StopCondidionError = TWIError;
+    type SendHeaderError = TWIError;
+    type SendError = TWIError;
+    type ReadError = TWIError;
+
+    fn init(&self) {
+        self.twi.init();
+    }
+
+    fn start_condition(&self) -> Result<(), Self::StartConditionError> {
+        self.context
+            .check(self.twi.start_condition_in(&self.context))
+    }
+
+    fn stop_condition(&self) -> Result<(), Self::StopCondidionError> {
+        self.context
+            .check(self.twi.stop_condition_in(&self.context))
+    }
+
+    fn send_header(
+        &self,
+        address: impl Into<SevenBitAddress>,
+        direction: Direction,
+    ) -> Result<(), Self::SendHeaderError> {
+        self.context.check(
+            self.twi
+                .send_header_in(&self.context, address.into(), direction),
+        )
+    }
+
+    fn send(&self, data: &[u8]) -> Result<(), Self::SendError> {
+        self.context.check(self.twi.send_in(&self.context, data))
+    }
+
+    fn read(&self, data: &mut [u8], end_with_nack: bool) -> Result<(), Self::ReadError> {
+        self.context
+            .check(self.twi.read_in(&self.context, data, end_with_nack))
+    }
+
+    fn read_with(&self, f: impl FnMut(usize, u8) -> bool) -> Result<usize, Self::ReadError> {
+        self.context.check(self.twi.read_with_in(&self.context, f))
+    }
+
+    #[cfg(feature = "transaction-observer")]
+    fn after_transaction(
+        &self,
+        result: Result<(), TransactionError<Self>>,
+    ) -> Result<(), TransactionError<Self>> {
+        self.twi
+            .after_transaction(result.map_err(TransactionError::rewrap))
+            .map_err(TransactionError::rewrap)
+    }
+}
+
+impl From<TransactionError<TimedTWI<'_>>> for TransactionError<TWI> {
+    fn from(error: TransactionError<TimedTWI<'_>>) -> Self {
+        error.rewrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::vec::Vec;
+
+    /// Returns the TWEA settings of the bytes of a read of `count` bytes
+    fn read_acks(count: usize, end_with_nack: bool) -> Vec<bool> {
+        (0..count)
+            .map(|index| read_control(index, count, end_with_nack) & TWEA != 0)
+            .collect()
+    }
+
+    #[test]
+    fn read_nacks_only_the_last_byte() {
+        assert_eq!(read_acks(1, true), [false]);
+        assert_eq!(read_acks(2, true), [true, false]);
+        assert_eq!(read_acks(3, true), [true, true, false]);
+    }
+
+    #[test]
+    fn read_without_nack_acks_every_byte() {
+        assert_eq!(read_acks(1, false), [true]);
+        assert_eq!(read_acks(2, false), [true, true]);
+        assert_eq!(read_acks(3, false), [true, true, true]);
+    }
+
+    #[test]
+    fn consecutive_reads_do_not_leak_twea_state() {
+        let acks: Vec<bool> = [(3, true), (3, true), (2, false), (2, true)]
+            .into_iter()
+            .flat_map(|(count, end_with_nack)| read_acks(count, end_with_nack))
+            .collect();
+        assert_eq!(
+            acks,
+            [true, true, false, true, true, false, true, true, true, false]
+        );
+    }
+
+    #[test]
+    fn read_control_clears_twint_and_keeps_the_hardware_enabled() {
+        for index in 0..3 {
+            let control = read_control(index, 3, true);
+            assert_eq!(control & (TWINT | TWEN), TWINT | TWEN);
+            assert_eq!(control & (TWSTA | TWSTO), 0);
+        }
+    }
+
+    #[test]
+    fn empty_read_clocks_no_byte() {
+        // Any register access would fault on the host, so returning
+        // at all shows that no byte was clocked.
+        let twi = TWI::new(100_000);
+        assert_eq!(twi.read(&mut [], true), Ok(()));
+        assert_eq!(twi.read(&mut [], false), Ok(()));
+        assert_eq!(twi.with_max_transfer_len(0).read(&mut [], true), Ok(()));
+    }
+
+    #[test]
+    fn transient_errors_are_decided_by_their_kind() {
+        assert!(TWIError::from(TWIErrorKind::Timeout).is_transient());
+        assert!(TWIError::from(TWSRStatus::ArbitrationLost).is_transient());
+        assert!(!TWIError::from(TWIErrorKind::TransferTooLarge).is_transient());
+    }
+
+    #[test]
+    fn oversized_transfers_fail_without_a_snapshot() {
+        // The transfer is rejected before any register is touched,
+        // so no snapshot is captured for it.
+        let twi = TWI::new(100_000).with_max_transfer_len(1);
+        let error = twi.read(&mut [0; 2], true).unwrap_err();
+        assert_eq!(error.kind, TWIErrorKind::TransferTooLarge);
+        #[cfg(feature = "register-snapshot")]
+        assert_eq!(error.snapshot, None);
+    }
+
+    #[test]
+    fn init_clears_a_stale_twint_only_when_asked() {
+        let twi = TWI::new(100_000);
+        assert_eq!(twi.init_control(), TWEN);
+        assert_eq!(twi.with_clear_pending(true).init_control(), TWEN | TWINT);
+        let twi = TWI::new(100_000)
+            .with_clear_pending(true)
+            .with_auto_listen(true);
+        assert_eq!(twi.init_control(), TWEN | TWINT | TWEA);
+    }
+
+    #[test]
+    fn setting_the_prescaler_keeps_the_status_readable() {
+        for twps in 0..=3 {
+            let twsr = replace_prescaler(0x10 | 0x02, twps);
+            assert_eq!(twsr & 0x03, twps);
+            assert_eq!(decode_status(twsr), TWSRStatus::RepeatedStartTransmitted);
+        }
+    }
+
+    #[test]
+    fn reaches_only_frequencies_within_the_bit_rate_range() {
+        assert!(reaches_frequency(400_000, 0));
+        assert!(!reaches_frequency(1_000_000, 0));
+        assert!(!reaches_frequency(20_000, 0));
+        assert!(reaches_frequency(20_000, 1));
+        assert!(!reaches_frequency(0, 3));
+    }
+
+    #[test]
+    fn bit_rate_uses_the_lowest_prescaler_reaching_the_frequency() {
+        assert_eq!(bit_rate_for_frequency(100_000), (0, 72));
+        assert_eq!(bit_rate_for_frequency(25_000), (1, 78));
+    }
+
+    #[test]
+    fn bit_rate_never_exceeds_the_requested_frequency() {
+        for freq in [490, 1_000, 10_000, 25_000, 30_000, 99_999, 400_000] {
+            let (twps, twbr) = bit_rate_for_frequency(freq);
+            assert!(F_CPU / (16 + 2 * ((twbr as u32) << (2 * twps))) <= freq);
+        }
+    }
+
+    #[test]
+    fn bit_rate_saturates_out_of_range_frequencies() {
+        assert_eq!(bit_rate_for_frequency(0), (3, u8::MAX));
+        assert_eq!(bit_rate_for_frequency(1), (3, u8::MAX));
+        assert_eq!(bit_rate_for_frequency(u32::MAX), (0, MIN_TWBR));
+        assert_eq!(clamp_twbr(twbr_for_frequency(25_000, 0)), u8::MAX);
+    }
 }
@@ -0,0 +1,233 @@
+//! A hardware implementation of I2C slave mode for the ATMega328P.
+
+use crate::{Direction, SevenBitAddress};
+
+use super::{
+    await_hardware, read_status, write_register, TWSRStatus, TWAR, TWCR, TWDR, TWEA, TWEN, TWGCE,
+    TWIE, TWINT, TWSTO,
+};
+
+/// Represents the hardware 2-wire interface in slave mode
+pub struct TWISlave {
+    pub twar: u8,
+}
+
+impl TWISlave {
+    /// Creates a new TWISlave instance
+    ///
+    /// The slave responds to `address`, and additionally
+    /// to the general call address if `general_call` is set.
+    /// Taking a [`SevenBitAddress`] ensures it fits in the upper
+    /// seven bits of TWAR.
+    pub const fn new(address: SevenBitAddress, general_call: bool) -> Self {
+        Self {
+            twar: (address.get() << 1) | if general_call { TWGCE } else { 0 },
+        }
+    }
+
+    /// Initializes the [`TWISlave`] and starts listening
+    /// for its own address
+    ///
+    /// [`TWISlave`]: Self
+    pub fn init(&self) {
+        write_register(TWAR, self.twar);
+        write_register(TWCR, TWEA | TWEN);
+    }
+
+    /// Enables or disables recognition of the general call address
+    ///
+    /// The own address in TWAR is left untouched, so the slave
+    /// keeps responding to it either way.
+    pub fn set_general_call_enabled(&self, enabled: bool) {
+        let twar = unsafe { TWAR.read_volatile() } & !TWGCE;
+        write_register(TWAR, twar | if enabled { TWGCE } else { 0 });
+    }
+
+    /// Re-arms the hardware to recognize its own address
+    pub fn listen(&self) {
+        write_register(TWCR, TWINT | TWEA | TWEN);
+    }
+
+    /// Returns whether a master has just addressed this slave
+    ///
+    /// Decodes the current status without touching the bus.
+    /// Returns the direction of the master's header if the
+    /// hardware reports that the own address or the general
+    /// call address was received, including after losing
+    /// arbitration as a master; otherwise returns `None`.
+    pub fn is_addressed(&self) -> Option<Direction> {
+        if unsafe { TWCR.read_volatile() } & TWINT == 0 {
+            return None;
+        }
+        match read_status() {
+            TWSRStatus::OwnWriteHeaderReceivedAckTransmitted
+            | TWSRStatus::ArbitrationLostOwnWriteHeaderReceived
+            | TWSRStatus::GeneralCallReceivedAckTransmitted
+            | TWSRStatus::ArbitrationLostGeneralCallReceived => Some(Direction::Write),
+            TWSRStatus::OwnReadHeaderReceivedAckTransmitted
+            | TWSRStatus::ArbitrationLostOwnReadHeaderReceived => Some(Direction::Read),
+            _ => None,
+        }
+    }
+
+    /// Loads the next byte to transmit to a master reading this slave
+    ///
+    /// This must be called once the hardware reports that the
+    /// slave was addressed for reading, or that the previous byte
+    /// was transmitted and acknowledged. It writes TWDR before
+    /// clearing TWINT, as the hardware requires.
+    ///
+    /// If `last` is not set, the slave expects the master to
+    /// acknowledge the byte and read another one. If it is set,
+    /// TWEA is cleared so the slave stops transmitting after this
+    /// byte whatever the master answers; [`listen`] must then be
+    /// called once the hardware reports the byte as transmitted,
+    /// to recognize the own address again.
+    ///
+    /// [`listen`]: Self::listen
+    pub fn load_response_byte(&self, byte: u8, last: bool) {
+        write_register(TWDR, byte);
+        write_register(TWCR, TWINT | TWEN | if last { 0 } else { TWEA });
+    }
+
+    /// Receives data written to this slave by a master
+    ///
+    /// Waits until the slave is addressed for writing, then
+    /// receives bytes into `data` until the master issues a
+    /// stop or repeated start condition. The byte filling the
+    /// last slot of `data` is answered with a NACK. Bytes the
+    /// hardware reports beyond the end of `data` are discarded.
+    ///
+    /// Returns the number of bytes received. The hardware is
+    /// re-armed to listen for its own address on return.
+    pub fn receive(&self, data: &mut [u8]) -> Result<usize, TWSRStatus> {
+        await_hardware();
+        match read_status() {
+            TWSRStatus::OwnWriteHeaderReceivedAckTransmitted
+            | TWSRStatus::ArbitrationLostOwnWriteHeaderReceived => (),
+            x => {
+                self.listen();
+                return Err(x);
+            }
+        }
+        let mut count = 0;
+        loop {
+            let ack = count + 1 < data.len();
+            write_register(TWCR, TWINT | TWEN | if ack { TWEA } else { 0 });
+            await_hardware();
+            match read_status() {
+                TWSRStatus::SlaveDataReceivedAckTransmitted => (),
+                TWSRStatus::SlaveDataReceivedNackTransmitted => {
+                    if let Some(byte) = data.get_mut(count) {
+                        *byte = unsafe { TWDR.read_volatile() };
+                        count += 1;
+                    }
+                    self.listen();
+                    return Ok(count);
+                }
+                TWSRStatus::StopOrRepeatedStartReceived => {
+                    self.listen();
+                    return Ok(count);
+                }
+                x => {
+                    self.listen();
+                    return Err(x);
+                }
+            }
+            if let Some(byte) = data.get_mut(count) {
+                *byte = unsafe { TWDR.read_volatile() };
+                count += 1;
+            }
+        }
+    }
+}
+
+/// Represents the callbacks of an interrupt-driven slave
+///
+/// The callbacks are invoked from the TWI interrupt by
+/// [`TWISlaveInterrupt::service`]. The hardware stretches
+/// SCL until the interrupt is serviced, so the master is
+/// held off for as long as a callback runs: keep them short,
+/// and avoid anything that waits on other interrupts.
+pub trait SlaveHandler {
+    /// Called with each byte written to this slave by a master.
+    fn on_write_received(&mut self, byte: u8);
+
+    /// Called when a master reads a byte from this slave.
+    ///
+    /// Returns the byte to transmit.
+    fn on_read_requested(&mut self) -> u8;
+
+    /// Called when a master ends a write to this slave with
+    /// a stop or repeated start condition.
+    fn on_stop(&mut self) {}
+}
+
+/// Represents the hardware 2-wire interface in interrupt-driven slave mode
+///
+/// [`service`] must be called from the TWI interrupt handler,
+/// e.g. with `avr-device`:
+///
+/// ```ignore
+/// #[avr_device::interrupt(atmega328p)]
+/// fn TWI() {
+///     SLAVE.service(&mut *HANDLER.borrow_mut());
+/// }
+/// ```
+///
+/// Global interrupts must be enabled after [`init`].
+///
+/// [`service`]: Self::service
+/// [`init`]: Self::init
+pub struct TWISlaveInterrupt {
+    pub twar: u8,
+}
+
+impl TWISlaveInterrupt {
+    /// Creates a new TWISlaveInterrupt instance
+    ///
+    /// The slave responds to `address`, and additionally
+    /// to the general call address if `general_call` is set.
+    /// Taking a [`SevenBitAddress`] ensures it fits in the upper
+    /// seven bits of TWAR.
+    pub const fn new(address: SevenBitAddress, general_call: bool) -> Self {
+        Self {
+            twar: (address.get() << 1) | if general_call { TWGCE } else { 0 },
+        }
+    }
+
+    /// Initializes the [`TWISlaveInterrupt`], enabling the TWI
+    /// interrupt and listening for its own address
+    ///
+    /// [`TWISlaveInterrupt`]: Self
+    pub fn init(&self) {
+        write_register(TWAR, self.twar);
+        write_register(TWCR, TWEA | TWEN | TWIE);
+    }
+
+    /// Services the TWI interrupt, dispatching to `handler`
+    ///
+    /// Every received byte is acknowledged, and the hardware
+    /// keeps listening for its own address afterwards. A bus
+    /// error releases the lines and resets the hardware state.
+    pub fn service(&self, handler: &mut impl SlaveHandler) {
+        let mut control = TWINT | TWEA | TWEN | TWIE;
+        match read_status() {
+            TWSRStatus::SlaveDataReceivedAckTransmitted
+            | TWSRStatus::SlaveDataReceivedNackTransmitted
+            | TWSRStatus::GeneralCallDataReceivedAckTransmitted
+            | TWSRStatus::GeneralCallDataReceivedNackTransmitted => {
+                handler.on_write_received(unsafe { TWDR.read_volatile() });
+            }
+            TWSRStatus::StopOrRepeatedStartReceived => handler.on_stop(),
+            TWSRStatus::OwnReadHeaderReceivedAckTransmitted
+            | TWSRStatus::ArbitrationLostOwnReadHeaderReceived
+            | TWSRStatus::SlaveDataTransmittedAckReceived => {
+                write_register(TWDR, handler.on_read_requested());
+            }
+            TWSRStatus::BusError => control |= TWSTO,
+            _ => (),
+        }
+        write_register(TWCR, control);
+    }
+}
@@ -0,0 +1,61 @@
+//! SMBus protocols on top of the ATMega328P hardware implementation.
+
+use crate::SevenBitAddress;
+
+use super::{TWISlave, TWSRStatus};
+
+/// The SMBus host address
+pub const SMBUS_HOST_ADDRESS: SevenBitAddress = match SevenBitAddress::new(0x08) {
+    Some(address) => address,
+    None => panic!(),
+};
+
+/// Represents an SMBus host
+pub struct SMBus {
+    slave: TWISlave,
+}
+
+impl SMBus {
+    /// Creates a new SMBus instance
+    ///
+    /// The host listens as a slave on [`SMBUS_HOST_ADDRESS`].
+    pub const fn new() -> Self {
+        Self {
+            slave: TWISlave::new(SMBUS_HOST_ADDRESS, false),
+        }
+    }
+
+    /// Initializes the [`SMBus`] and starts listening
+    /// for Host Notify messages
+    ///
+    /// [`SMBus`]: Self
+    pub fn init(&self) {
+        self.slave.init();
+    }
+
+    /// Services a single incoming Host Notify message
+    ///
+    /// Waits until a device writes to the host address, then
+    /// calls `handler` with the notifying device's 7-bit
+    /// address and the 16-bit data word.
+    ///
+    /// Messages that are not exactly three bytes long are
+    /// not valid Host Notify messages and are discarded
+    /// without calling `handler`.
+    pub fn on_host_notify(&mut self, mut handler: impl FnMut(u8, u16)) -> Result<(), TWSRStatus> {
+        let mut message = [0; 4];
+        if self.slave.receive(&mut message)? == 3 {
+            handler(
+                message[0] >> 1,
+                u16::from_le_bytes([message[1], message[2]]),
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Default for SMBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
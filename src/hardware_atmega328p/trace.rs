@@ -0,0 +1,79 @@
+//! Byte-level tracing of transactions on the ATMega328P hardware implementation.
+
+use super::TWSRStatus;
+
+/// Represents an event of a traced transaction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraceEvent {
+    /// A header or data byte was sent
+    Sent(u8),
+    /// A data byte was received
+    Received(u8),
+    /// A status was decoded after an operation
+    Status(TWSRStatus),
+}
+
+/// Records the events of a traced transaction
+pub(super) trait Trace {
+    fn record(&mut self, event: TraceEvent);
+}
+
+impl<T: Trace + ?Sized> Trace for &mut T {
+    fn record(&mut self, event: TraceEvent) {
+        (**self).record(event);
+    }
+}
+
+/// Represents a bounded buffer of [`TraceEvent`]s
+///
+/// At most `N` events are captured; later events are dropped.
+pub struct TraceBuffer<const N: usize> {
+    events: [TraceEvent; N],
+    len: usize,
+    overflowed: bool,
+}
+
+impl<const N: usize> TraceBuffer<N> {
+    /// Creates a new empty TraceBuffer instance
+    pub const fn new() -> Self {
+        Self {
+            events: [TraceEvent::Sent(0); N],
+            len: 0,
+            overflowed: false,
+        }
+    }
+
+    /// Returns the captured events, oldest first
+    pub fn events(&self) -> &[TraceEvent] {
+        &self.events[..self.len]
+    }
+
+    /// Returns whether events were dropped because the buffer was full
+    pub fn overflowed(&self) -> bool {
+        self.overflowed
+    }
+
+    /// Removes all captured events
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.overflowed = false;
+    }
+}
+
+impl<const N: usize> Default for TraceBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Trace for TraceBuffer<N> {
+    fn record(&mut self, event: TraceEvent) {
+        match self.events.get_mut(self.len) {
+            Some(slot) => {
+                *slot = event;
+                self.len += 1;
+            }
+            None => self.overflowed = true,
+        }
+    }
+}
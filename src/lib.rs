@@ -5,6 +5,14 @@
 #[cfg(feature = "hardware-atmega328p")]
 pub mod hardware_atmega328p;
 
+#[cfg(feature = "plan")]
+pub mod plan;
+
+#[cfg(feature = "register-map")]
+pub mod register_map;
+
+use core::fmt;
+
 /// Represents data direction used for the R/W bit in the I2C header.
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -13,6 +21,77 @@ pub enum Direction {
     Write,
 }
 
+/// Represents a 7-bit I2C address, as sent in the I2C header.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SevenBitAddress(u8);
+
+impl SevenBitAddress {
+    /// Creates a new [`SevenBitAddress`].
+    ///
+    /// Returns `None` if `address` does not fit in seven bits.
+    pub const fn new(address: u8) -> Option<Self> {
+        if address <= 0x7F {
+            Some(Self(address))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the address as a `u8`.
+    pub const fn get(self) -> u8 {
+        self.0
+    }
+}
+
+/// Represents a `u8` that does not fit in seven bits, and so
+/// is not a [`SevenBitAddress`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AddressOutOfRange(pub u8);
+
+impl TryFrom<u8> for SevenBitAddress {
+    type Error = AddressOutOfRange;
+
+    /// Converts a 7-bit address given as a `u8`.
+    ///
+    /// Use [`EightBitAddress`] for datasheets that give the
+    /// address already shifted.
+    fn try_from(address: u8) -> Result<Self, Self::Error> {
+        Self::new(address).ok_or(AddressOutOfRange(address))
+    }
+}
+
+impl From<EightBitAddress> for SevenBitAddress {
+    fn from(address: EightBitAddress) -> Self {
+        Self(address.0 >> 1)
+    }
+}
+
+/// Represents an 8-bit I2C address, i.e. a 7-bit address shifted
+/// left by one with the R/W bit zeroed.
+///
+/// Some datasheets give addresses in this form.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EightBitAddress(u8);
+
+impl EightBitAddress {
+    /// Creates a new [`EightBitAddress`].
+    ///
+    /// Returns `None` if the R/W bit of `address` is set, as
+    /// such a value is not an address but a read header.
+    pub const fn new(address: u8) -> Option<Self> {
+        if address & 0x01 == 0 {
+            Some(Self(address))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the address as a `u8`.
+    pub const fn get(self) -> u8 {
+        self.0
+    }
+}
+
 /// Represents an I2C driver.
 pub trait I2CBus {
     /// An error type for the [`start_condition`] method.
@@ -40,6 +119,13 @@ pub trait I2CBus {
     /// [`read`]: Self::read
     type ReadError;
 
+    /// Whether the bus can issue a repeated start condition.
+    ///
+    /// This decides how [`transaction`] separates operations.
+    ///
+    /// [`transaction`]: Self::transaction
+    const SUPPORTS_REPEATED_START: bool = true;
+
     /// Initializes the [`I2CBus`]
     ///
     /// [`I2CBus`]: Self
@@ -52,11 +138,1290 @@ pub trait I2CBus {
     fn stop_condition(&self) -> Result<(), Self::StopCondidionError>;
 
     /// Sends an I2C header to the bus.
-    fn send_header(&self, address: u8, direction: Direction) -> Result<(), Self::SendHeaderError>;
+    ///
+    /// `address` is a [`SevenBitAddress`], or anything that converts
+    /// into one, such as an [`EightBitAddress`]. A plain `u8` must be
+    /// checked first, e.g. with [`SevenBitAddress::new`]; the provided
+    /// helpers accept one and fail with
+    /// [`TransactionError::InvalidAddress`] if it does not fit.
+    fn send_header(
+        &self,
+        address: impl Into<SevenBitAddress>,
+        direction: Direction,
+    ) -> Result<(), Self::SendHeaderError>;
 
     /// Sends data to an I2C slave.
     fn send(&self, data: &[u8]) -> Result<(), Self::SendError>;
 
     /// Reads data from an I2C slave.
+    ///
+    /// Every byte is answered with an ACK, except the last one,
+    /// which is answered with a NACK if `end_with_nack` is set.
+    ///
+    /// If `data` is empty, no byte is clocked and `Ok` is returned
+    /// without touching the bus, regardless of `end_with_nack`. As
+    /// with any read, the caller remains responsible for the
+    /// surrounding start condition, header and stop condition; note
+    /// that a stop cannot follow an acknowledged read header before
+    /// a byte is NACKed, which is why [`read_from`] addresses an
+    /// empty read in the write direction.
+    ///
+    /// [`read_from`]: Self::read_from
     fn read(&self, data: &mut [u8], end_with_nack: bool) -> Result<(), Self::ReadError>;
+
+    /// Called with the result of every transaction performed by the
+    /// provided helpers, such as [`write_to`] or [`transaction`],
+    /// which then return the result it returns.
+    ///
+    /// This is a hook for logging or counting transaction outcomes
+    /// in one place; it returns `result` unchanged by default. Bus
+    /// wrappers such as [`Translated`] forward it to the bus they
+    /// wrap, re-wrapping the result with [`TransactionError::rewrap`].
+    ///
+    /// [`write_to`]: Self::write_to
+    /// [`transaction`]: Self::transaction
+    fn after_transaction(
+        &self,
+        result: Result<(), TransactionError<Self>>,
+    ) -> Result<(), TransactionError<Self>> {
+        result
+    }
+
+    /// Reads data from an I2C slave, answering every byte,
+    /// including the last one, with an ACK.
+    ///
+    /// UM10204 §3.1.6 requires the last byte of a read to be
+    /// NACKed before either a stop or a repeated start, so this is
+    /// only for devices known to tolerate an ACK there. A slave
+    /// left transmitting after an ACK can hold SDA low and hang the
+    /// bus. Use [`read`] with `end_with_nack` set instead, or end
+    /// such a read with `TWI::end_read` of the `hardware_atmega328p`
+    /// module, which clocks one NACKed dummy byte before the stop.
+    ///
+    /// [`read`]: Self::read
+    fn read_ack_all(&self, data: &mut [u8]) -> Result<(), Self::ReadError> {
+        self.read(data, false)
+    }
+
+    /// Reads data from an I2C slave one byte at a time, until `f`
+    /// returns `false`.
+    ///
+    /// `f` is called with the index and value of each byte read, and
+    /// returns whether to continue reading. Since every byte is
+    /// acknowledged before `f` sees it, one final dummy byte is clocked
+    /// and answered with a NACK to release the slave; it is not passed
+    /// to `f`. Returns the number of bytes passed to `f`.
+    ///
+    /// `f` is [`FnMut`], so it can keep state across bytes, e.g. a
+    /// running checksum that marks the end of a frame:
+    ///
+    /// ```
+    /// use avr_i2c::I2CBus;
+    ///
+    /// fn read_frame<B: I2CBus>(bus: &B) -> Result<usize, B::ReadError> {
+    ///     let mut checksum = 0u8;
+    ///     bus.read_with(|_, byte| {
+    ///         checksum = checksum.wrapping_add(byte);
+    ///         checksum != 0
+    ///     })
+    /// }
+    /// ```
+    fn read_with(&self, mut f: impl FnMut(usize, u8) -> bool) -> Result<usize, Self::ReadError> {
+        let mut byte = [0];
+        let mut count = 0;
+        loop {
+            self.read(&mut byte, false)?;
+            let more = f(count, byte[0]);
+            count += 1;
+            if !more {
+                self.read(&mut byte, true)?;
+                return Ok(count);
+            }
+        }
+    }
+
+    /// Writes data to the slave at `address` in a single transaction.
+    ///
+    /// Once the start condition succeeds, a stop condition is
+    /// always issued, even if a later step fails.
+    fn write_to(
+        &self,
+        address: impl TryInto<SevenBitAddress>,
+        data: &[u8],
+    ) -> Result<(), TransactionError<Self>> {
+        let address = seven_bit_address(address)?;
+        let result = self
+            .start_condition()
+            .map_err(TransactionError::StartCondition)
+            .and_then(|_| {
+                let result = self
+                    .send_header(address, Direction::Write)
+                    .map_err(TransactionError::SendHeader)
+                    .and_then(|_| self.send(data).map_err(TransactionError::Send));
+                let stop = self
+                    .stop_condition()
+                    .map_err(TransactionError::StopCondition);
+                result.and(stop)
+            });
+        self.after_transaction(result)
+    }
+
+    /// Returns whether a slave acknowledges `address`.
+    ///
+    /// This writes no data to the slave: the header is
+    /// immediately followed by a stop condition.
+    fn probe(&self, address: impl TryInto<SevenBitAddress>) -> bool {
+        self.write_to(address, &[]).is_ok()
+    }
+
+    /// Reads data from the slave at `address` in a single transaction.
+    ///
+    /// The last byte is answered with a NACK. Once the start
+    /// condition succeeds, a stop condition is always issued,
+    /// even if a later step fails.
+    ///
+    /// If `data` is empty, this is equivalent to [`probe`], but
+    /// reports the error: the header is sent in the write direction.
+    /// After an acknowledged read header the slave drives SDA with
+    /// its first byte, which must be clocked and NACKed before a
+    /// stop condition can be issued, so zero bytes cannot be read.
+    ///
+    /// [`probe`]: Self::probe
+    fn read_from(
+        &self,
+        address: impl TryInto<SevenBitAddress>,
+        data: &mut [u8],
+    ) -> Result<(), TransactionError<Self>> {
+        let address = seven_bit_address(address)?;
+        if data.is_empty() {
+            return self.write_to(address, &[]);
+        }
+        let result = self
+            .start_condition()
+            .map_err(TransactionError::StartCondition)
+            .and_then(|_| {
+                let result = self
+                    .send_header(address, Direction::Read)
+                    .map_err(TransactionError::SendHeader)
+                    .and_then(|_| self.read(data, true).map_err(TransactionError::Read));
+                let stop = self
+                    .stop_condition()
+                    .map_err(TransactionError::StopCondition);
+                result.and(stop)
+            });
+        self.after_transaction(result)
+    }
+
+    /// Writes `write` to the slave at `address`, then reads `read`
+    /// from it after a repeated start.
+    ///
+    /// If `read` is empty, this is equivalent to [`write_to`].
+    /// Once the start condition succeeds, a stop condition is
+    /// always issued, even if a later step fails.
+    ///
+    /// [`write_to`]: Self::write_to
+    fn write_read(
+        &self,
+        address: impl TryInto<SevenBitAddress>,
+        write: &[u8],
+        read: &mut [u8],
+    ) -> Result<(), TransactionError<Self>> {
+        let address = seven_bit_address(address)?;
+        if read.is_empty() {
+            return self.write_to(address, write);
+        }
+        write_then_read(self, address, write, |bus| bus.read(read, true))
+    }
+
+    /// Reads consecutive bytes starting at `register` of the slave
+    /// at `address`.
+    fn read_register(
+        &self,
+        address: impl TryInto<SevenBitAddress>,
+        register: u8,
+        data: &mut [u8],
+    ) -> Result<(), TransactionError<Self>> {
+        self.write_read(address, &[register], data)
+    }
+
+    /// Reads consecutive bytes starting at `register` of a device that
+    /// selects functions through aliased addresses.
+    ///
+    /// The slave is addressed at `base_addr + addr_offset`. Fails
+    /// with [`TransactionError::InvalidAddress`] before any bus
+    /// activity if the sum is not a valid 7-bit address. For devices
+    /// whose "register" is the address alias itself, use
+    /// [`read_from`] with the computed address instead.
+    ///
+    /// [`read_from`]: Self::read_from
+    fn read_register_at(
+        &self,
+        base_addr: impl TryInto<SevenBitAddress>,
+        addr_offset: u8,
+        register: u8,
+        data: &mut [u8],
+    ) -> Result<(), TransactionError<Self>> {
+        let address = seven_bit_address(base_addr)?
+            .get()
+            .checked_add(addr_offset)
+            .and_then(SevenBitAddress::new)
+            .ok_or(TransactionError::InvalidAddress)?;
+        self.read_register(address, register, data)
+    }
+
+    /// Reads consecutive bytes starting at `start_reg` of the slave
+    /// at `dev`, in transactions of at most `max_burst` bytes.
+    ///
+    /// Unlike [`read_register`], which reads the whole range in a
+    /// single burst, each chunk is a separate transaction that
+    /// addresses its first register explicitly, so this does not
+    /// rely on the device auto-incrementing across the stop. Use it
+    /// for devices that NACK bursts beyond a fixed length. A
+    /// `max_burst` of zero is treated as one.
+    ///
+    /// [`read_register`]: Self::read_register
+    fn read_range(
+        &self,
+        dev: impl TryInto<SevenBitAddress>,
+        start_reg: u8,
+        out: &mut [u8],
+        max_burst: usize,
+    ) -> Result<(), TransactionError<Self>> {
+        let dev = seven_bit_address(dev)?;
+        let mut register = start_reg;
+        for chunk in out.chunks_mut(max_burst.max(1)) {
+            self.read_register(dev, register, chunk)?;
+            register = register.wrapping_add(chunk.len() as u8);
+        }
+        Ok(())
+    }
+
+    /// Writes consecutive bytes starting at `register` of the slave
+    /// at `address`.
+    fn write_register(
+        &self,
+        address: impl TryInto<SevenBitAddress>,
+        register: u8,
+        data: &[u8],
+    ) -> Result<(), TransactionError<Self>> {
+        let address = seven_bit_address(address)?;
+        let result = self
+            .start_condition()
+            .map_err(TransactionError::StartCondition)
+            .and_then(|_| {
+                let result = self
+                    .send_header(address, Direction::Write)
+                    .map_err(TransactionError::SendHeader)
+                    .and_then(|_| self.send(&[register]).map_err(TransactionError::Send))
+                    .and_then(|_| self.send(data).map_err(TransactionError::Send));
+                let stop = self
+                    .stop_condition()
+                    .map_err(TransactionError::StopCondition);
+                result.and(stop)
+            });
+        self.after_transaction(result)
+    }
+
+    /// Writes the bytes of `data` starting at `register` of the slave
+    /// at `address`.
+    ///
+    /// The register byte and `data` are sent one after the other,
+    /// so no `[u8; N + 1]` buffer has to be assembled; this is
+    /// [`write_register`] for fixed-size data.
+    ///
+    /// [`write_register`]: Self::write_register
+    fn write_register_gather<const N: usize>(
+        &self,
+        address: impl TryInto<SevenBitAddress>,
+        register: u8,
+        data: &[u8; N],
+    ) -> Result<(), TransactionError<Self>> {
+        self.write_register(address, register, data)
+    }
+
+    /// Performs `operations` in a single transaction.
+    ///
+    /// Every operation begins with its own header. Operations are
+    /// separated as decided by [`separator`]: a repeated start keeps
+    /// the bus between operations on the same device, while a stop
+    /// and a new start let another device's operation follow.
+    /// Reads end with a NACK, and an empty read is addressed in the
+    /// write direction, as [`read_from`] does. Once the first start
+    /// condition succeeds, a stop condition is always issued, even
+    /// if a later step fails.
+    ///
+    /// Fails with [`TransactionError::InvalidAddress`] before any
+    /// bus activity if the address of an operation does not fit in
+    /// seven bits.
+    ///
+    /// [`read_from`]: Self::read_from
+    fn transaction(&self, operations: &mut [Operation<'_>]) -> Result<(), TransactionError<Self>> {
+        for operation in operations.iter() {
+            seven_bit_address::<Self>(operation.address())?;
+        }
+        let mut operations = operations.iter_mut();
+        let Some(first) = operations.next() else {
+            return Ok(());
+        };
+        let result = self
+            .start_condition()
+            .map_err(TransactionError::StartCondition)
+            .and_then(|_| {
+                let mut previous = first.address();
+                let mut result = perform(self, first);
+                for operation in operations {
+                    if result.is_err() {
+                        break;
+                    }
+                    let address = operation.address();
+                    result = match separator(previous, address, Self::SUPPORTS_REPEATED_START) {
+                        Separator::RepeatedStart => Ok(()),
+                        Separator::StopAndStart => self
+                            .stop_condition()
+                            .map_err(TransactionError::StopCondition),
+                    }
+                    .and_then(|_| {
+                        self.start_condition()
+                            .map_err(TransactionError::StartCondition)
+                    })
+                    .and_then(|_| perform(self, operation));
+                    previous = address;
+                }
+                let stop = self
+                    .stop_condition()
+                    .map_err(TransactionError::StopCondition);
+                result.and(stop)
+            });
+        self.after_transaction(result)
+    }
+
+    /// Broadcasts a general call software reset to every device on the bus.
+    ///
+    /// Every device implementing the general call reset resets as if
+    /// it was power-cycled, losing its configuration; this cannot be
+    /// undone. Devices that do not implement it ignore the broadcast,
+    /// and if no device acknowledges the general call, a
+    /// [`TransactionError::SendHeader`] error is returned.
+    fn reset_all_devices(&self) -> Result<(), TransactionError<Self>> {
+        self.write_to(GENERAL_CALL_ADDRESS, &[GENERAL_CALL_RESET])
+    }
+
+    /// Writes a 16-bit value to `register` of the slave at `address`,
+    /// least significant byte first.
+    fn write_u16_le(
+        &self,
+        address: impl TryInto<SevenBitAddress>,
+        register: u8,
+        value: u16,
+    ) -> Result<(), TransactionError<Self>> {
+        self.write_register(address, register, &value.to_le_bytes())
+    }
+
+    /// Writes a 16-bit value to `register` of the slave at `address`,
+    /// most significant byte first.
+    fn write_u16_be(
+        &self,
+        address: impl TryInto<SevenBitAddress>,
+        register: u8,
+        value: u16,
+    ) -> Result<(), TransactionError<Self>> {
+        self.write_register(address, register, &value.to_be_bytes())
+    }
+
+    /// Writes a 32-bit value to `register` of the slave at `address`,
+    /// least significant byte first.
+    fn write_u32_le(
+        &self,
+        address: impl TryInto<SevenBitAddress>,
+        register: u8,
+        value: u32,
+    ) -> Result<(), TransactionError<Self>> {
+        self.write_register(address, register, &value.to_le_bytes())
+    }
+
+    /// Writes a 32-bit value to `register` of the slave at `address`,
+    /// most significant byte first.
+    fn write_u32_be(
+        &self,
+        address: impl TryInto<SevenBitAddress>,
+        register: u8,
+        value: u32,
+    ) -> Result<(), TransactionError<Self>> {
+        self.write_register(address, register, &value.to_be_bytes())
+    }
+
+    /// Reads `register` of the slave at `address` `samples` times,
+    /// returning its value only if every read agrees.
+    ///
+    /// `samples` is clamped to `1..=`[`MAX_STABLE_SAMPLES`] to keep
+    /// the bus time bounded. Fails with [`StableReadError::Unstable`]
+    /// on the first read that disagrees with the previous ones.
+    fn read_stable(
+        &self,
+        address: impl TryInto<SevenBitAddress>,
+        register: u8,
+        samples: u8,
+    ) -> Result<u8, StableReadError<Self>> {
+        let address = seven_bit_address(address).map_err(StableReadError::Transaction)?;
+        let mut value = [0];
+        self.read_register(address, register, &mut value)
+            .map_err(StableReadError::Transaction)?;
+        for _ in 1..samples.clamp(1, MAX_STABLE_SAMPLES) {
+            let mut sample = [0];
+            self.read_register(address, register, &mut sample)
+                .map_err(StableReadError::Transaction)?;
+            if sample != value {
+                return Err(StableReadError::Unstable);
+            }
+        }
+        Ok(value[0])
+    }
+
+    /// Reads a null-terminated string starting at `register` of the
+    /// slave at `address` into `out`.
+    ///
+    /// Bytes are read with [`read_with`] until the terminator, which
+    /// is not stored, or until `out` is full, in which case the
+    /// string is returned truncated to `out`. An empty `out` returns
+    /// an empty string without touching the bus. Fails with
+    /// [`CStrReadError::InvalidUtf8`] if the bytes read are not
+    /// valid UTF-8.
+    ///
+    /// Once the start condition succeeds, a stop condition is
+    /// always issued, even if a later step fails.
+    ///
+    /// [`read_with`]: Self::read_with
+    fn read_cstr<'a>(
+        &self,
+        address: impl TryInto<SevenBitAddress>,
+        register: u8,
+        out: &'a mut [u8],
+    ) -> Result<&'a str, CStrReadError<Self>> {
+        if out.is_empty() {
+            return Ok("");
+        }
+        let address = seven_bit_address(address).map_err(CStrReadError::Transaction)?;
+        let mut len = 0;
+        write_then_read(self, address, &[register], |bus| {
+            bus.read_with(|index, byte| {
+                if byte == 0 {
+                    return false;
+                }
+                out[index] = byte;
+                len = index + 1;
+                len < out.len()
+            })
+            .map(|_| ())
+        })
+        .map_err(CStrReadError::Transaction)?;
+        core::str::from_utf8(&out[..len]).map_err(|_| CStrReadError::InvalidUtf8)
+    }
+}
+
+/// Represents a mapping of logical device addresses to physical
+/// bus addresses, e.g. by an address-translating bridge.
+pub trait AddressTranslator {
+    /// Returns the physical address of the device at the logical `address`.
+    fn translate(&self, address: SevenBitAddress) -> SevenBitAddress;
+}
+
+impl<F: Fn(SevenBitAddress) -> SevenBitAddress> AddressTranslator for F {
+    fn translate(&self, address: SevenBitAddress) -> SevenBitAddress {
+        self(address)
+    }
+}
+
+/// Represents an [`I2CBus`] whose devices are addressed through an
+/// [`AddressTranslator`].
+///
+/// Every header is sent to the translated address, so device drivers
+/// can be written against logical addresses. Since [`Translated`] is
+/// itself an [`I2CBus`], it composes with other bus wrappers, and
+/// since `&B` is an [`I2CBus`] too, it can wrap a borrowed bus.
+pub struct Translated<B: I2CBus, T: AddressTranslator> {
+    bus: B,
+    translator: T,
+}
+
+impl<B: I2CBus, T: AddressTranslator> Translated<B, T> {
+    /// Creates a new [`Translated`] bus sending headers on `bus`
+    /// to the addresses translated by `translator`.
+    pub const fn new(bus: B, translator: T) -> Self {
+        Self { bus, translator }
+    }
+
+    /// Returns the underlying bus.
+    pub const fn bus(&self) -> &B {
+        &self.bus
+    }
+
+    /// Returns the address translator.
+    pub const fn translator(&self) -> &T {
+        &self.translator
+    }
+}
+
+impl<B: I2CBus, T: AddressTranslator> I2CBus for Translated<B, T> {
+    type StartConditionError = B::StartConditionError;
+    type StopCondidionError = B::StopCondidionError;
+    type SendHeaderError = B::SendHeaderError;
+    type SendError = B::SendError;
+    type ReadError = B::ReadError;
+
+    const SUPPORTS_REPEATED_START: bool = B::SUPPORTS_REPEATED_START;
+
+    fn init(&self) {
+        self.bus.init();
+    }
+
+    fn start_condition(&self) -> Result<(), Self::StartConditionError> {
+        self.bus.start_condition()
+    }
+
+    fn stop_condition(&self) -> Result<(), Self::StopCondidionError> {
+        self.bus.stop_condition()
+    }
+
+    fn send_header(
+        &self,
+        address: impl Into<SevenBitAddress>,
+        direction: Direction,
+    ) -> Result<(), Self::SendHeaderError> {
+        self.bus
+            .send_header(self.translator.translate(address.into()), direction)
+    }
+
+    fn send(&self, data: &[u8]) -> Result<(), Self::SendError> {
+        self.bus.send(data)
+    }
+
+    fn read(&self, data: &mut [u8], end_with_nack: bool) -> Result<(), Self::ReadError> {
+        self.bus.read(data, end_with_nack)
+    }
+
+    fn read_with(&self, f: impl FnMut(usize, u8) -> bool) -> Result<usize, Self::ReadError> {
+        self.bus.read_with(f)
+    }
+
+    fn after_transaction(
+        &self,
+        result: Result<(), TransactionError<Self>>,
+    ) -> Result<(), TransactionError<Self>> {
+        self.bus
+            .after_transaction(result.map_err(TransactionError::rewrap))
+            .map_err(TransactionError::rewrap)
+    }
+}
+
+impl<B: I2CBus + ?Sized> I2CBus for &B {
+    type StartConditionError = B::StartConditionError;
+    type StopCondidionError = B::StopCondidionError;
+    type SendHeaderError = B::SendHeaderError;
+    type SendError = B::SendError;
+    type ReadError = B::ReadError;
+
+    const SUPPORTS_REPEATED_START: bool = B::SUPPORTS_REPEATED_START;
+
+    fn init(&self) {
+        (**self).init();
+    }
+
+    fn start_condition(&self) -> Result<(), Self::StartConditionError> {
+        (**self).start_condition()
+    }
+
+    fn stop_condition(&self) -> Result<(), Self::StopCondidionError> {
+        (**self).stop_condition()
+    }
+
+    fn send_header(
+        &self,
+        address: impl Into<SevenBitAddress>,
+        direction: Direction,
+    ) -> Result<(), Self::SendHeaderError> {
+        (**self).send_header(address, direction)
+    }
+
+    fn send(&self, data: &[u8]) -> Result<(), Self::SendError> {
+        (**self).send(data)
+    }
+
+    fn read(&self, data: &mut [u8], end_with_nack: bool) -> Result<(), Self::ReadError> {
+        (**self).read(data, end_with_nack)
+    }
+
+    fn read_with(&self, f: impl FnMut(usize, u8) -> bool) -> Result<usize, Self::ReadError> {
+        (**self).read_with(f)
+    }
+
+    fn after_transaction(
+        &self,
+        result: Result<(), TransactionError<Self>>,
+    ) -> Result<(), TransactionError<Self>> {
+        (**self)
+            .after_transaction(result.map_err(TransactionError::rewrap))
+            .map_err(TransactionError::rewrap)
+    }
+}
+
+/// Represents a single operation of an [`I2CBus::transaction`].
+#[derive(Debug, PartialEq)]
+pub enum Operation<'a> {
+    Write { address: u8, data: &'a [u8] },
+    Read { address: u8, data: &'a mut [u8] },
+}
+
+impl Operation<'_> {
+    /// Returns the address of the slave the operation targets.
+    pub fn address(&self) -> u8 {
+        match self {
+            Self::Write { address, .. } | Self::Read { address, .. } => *address,
+        }
+    }
+}
+
+/// Represents how two consecutive operations of a transaction are separated.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Separator {
+    RepeatedStart,
+    StopAndStart,
+}
+
+/// Decides how an operation on `next` follows an operation on `previous`.
+///
+/// Operations on the same device are separated by a repeated start if
+/// the bus supports it, so the device sees one uninterrupted sequence.
+/// Otherwise, the bus is released with a stop before the next start.
+pub const fn separator(previous: u8, next: u8, supports_repeated_start: bool) -> Separator {
+    if previous == next && supports_repeated_start {
+        Separator::RepeatedStart
+    } else {
+        Separator::StopAndStart
+    }
+}
+
+/// Sends the header of `operation` on `bus`, then performs it.
+///
+/// This is a building block of [`I2CBus::transaction`], and
+/// assumes a start condition was just issued.
+fn perform<B: I2CBus + ?Sized>(
+    bus: &B,
+    operation: &mut Operation<'_>,
+) -> Result<(), TransactionError<B>> {
+    let address = seven_bit_address(operation.address())?;
+    match operation {
+        Operation::Write { data, .. } => {
+            bus.send_header(address, Direction::Write)
+                .map_err(TransactionError::SendHeader)?;
+            bus.send(data).map_err(TransactionError::Send)
+        }
+        Operation::Read { data: [], .. } => bus
+            .send_header(address, Direction::Write)
+            .map_err(TransactionError::SendHeader),
+        Operation::Read { data, .. } => {
+            bus.send_header(address, Direction::Read)
+                .map_err(TransactionError::SendHeader)?;
+            bus.read(data, true).map_err(TransactionError::Read)
+        }
+    }
+}
+
+/// Writes `write` to the slave at `address`, then reads from it
+/// with `read` after a repeated start.
+///
+/// This is the building block of [`I2CBus::write_read`] and
+/// [`I2CBus::read_cstr`]. Once the start condition succeeds, a stop
+/// condition is always issued, even if a later step fails.
+fn write_then_read<B: I2CBus + ?Sized>(
+    bus: &B,
+    address: SevenBitAddress,
+    write: &[u8],
+    read: impl FnOnce(&B) -> Result<(), B::ReadError>,
+) -> Result<(), TransactionError<B>> {
+    let result = bus
+        .start_condition()
+        .map_err(TransactionError::StartCondition)
+        .and_then(|_| {
+            let result = bus
+                .send_header(address, Direction::Write)
+                .map_err(TransactionError::SendHeader)
+                .and_then(|_| bus.send(write).map_err(TransactionError::Send))
+                .and_then(|_| {
+                    bus.start_condition()
+                        .map_err(TransactionError::StartCondition)
+                })
+                .and_then(|_| {
+                    bus.send_header(address, Direction::Read)
+                        .map_err(TransactionError::SendHeader)
+                })
+                .and_then(|_| read(bus).map_err(TransactionError::Read));
+            let stop = bus
+                .stop_condition()
+                .map_err(TransactionError::StopCondition);
+            result.and(stop)
+        });
+    bus.after_transaction(result)
+}
+
+/// The general call address, addressing every device on the bus
+pub const GENERAL_CALL_ADDRESS: u8 = 0x00;
+
+/// The general call command resetting every device supporting it
+pub const GENERAL_CALL_RESET: u8 = 0x06;
+
+/// The maximum number of samples taken by [`I2CBus::read_stable`]
+pub const MAX_STABLE_SAMPLES: u8 = 16;
+
+/// Represents an error produced by one of the steps
+/// of a composed [`I2CBus`] transaction.
+pub enum TransactionError<B: I2CBus + ?Sized> {
+    StartCondition(B::StartConditionError),
+    StopCondition(B::StopCondidionError),
+    SendHeader(B::SendHeaderError),
+    Send(B::SendError),
+    Read(B::ReadError),
+    /// An address does not fit in seven bits; the transaction
+    /// failed before any bus activity
+    InvalidAddress,
+}
+
+/// Converts the `address` given to a helper, failing with
+/// [`TransactionError::InvalidAddress`] if it does not fit
+/// in seven bits.
+fn seven_bit_address<B: I2CBus + ?Sized>(
+    address: impl TryInto<SevenBitAddress>,
+) -> Result<SevenBitAddress, TransactionError<B>> {
+    address
+        .try_into()
+        .map_err(|_| TransactionError::InvalidAddress)
+}
+
+impl<B: I2CBus + ?Sized> TransactionError<B> {
+    /// Re-wraps the error as an error of the bus `C`, which has
+    /// the same error types as `B`.
+    pub fn rewrap<C>(self) -> TransactionError<C>
+    where
+        C: I2CBus<
+                StartConditionError = B::StartConditionError,
+                StopCondidionError = B::StopCondidionError,
+                SendHeaderError = B::SendHeaderError,
+                SendError = B::SendError,
+                ReadError = B::ReadError,
+            > + ?Sized,
+    {
+        match self {
+            Self::StartCondition(x) => TransactionError::StartCondition(x),
+            Self::StopCondition(x) => TransactionError::StopCondition(x),
+            Self::SendHeader(x) => TransactionError::SendHeader(x),
+            Self::Send(x) => TransactionError::Send(x),
+            Self::Read(x) => TransactionError::Read(x),
+            Self::InvalidAddress => TransactionError::InvalidAddress,
+        }
+    }
+}
+
+impl<B> fmt::Debug for TransactionError<B>
+where
+    B: I2CBus + ?Sized,
+    B::StartConditionError: fmt::Debug,
+    B::StopCondidionError: fmt::Debug,
+    B::SendHeaderError: fmt::Debug,
+    B::SendError: fmt::Debug,
+    B::ReadError: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StartCondition(x) => f.debug_tuple("StartCondition").field(x).finish(),
+            Self::StopCondition(x) => f.debug_tuple("StopCondition").field(x).finish(),
+            Self::SendHeader(x) => f.debug_tuple("SendHeader").field(x).finish(),
+            Self::Send(x) => f.debug_tuple("Send").field(x).finish(),
+            Self::Read(x) => f.debug_tuple("Read").field(x).finish(),
+            Self::InvalidAddress => f.write_str("InvalidAddress"),
+        }
+    }
+}
+
+/// Represents an error of [`I2CBus::read_cstr`].
+pub enum CStrReadError<B: I2CBus + ?Sized> {
+    Transaction(TransactionError<B>),
+    InvalidUtf8,
+}
+
+impl<B> fmt::Debug for CStrReadError<B>
+where
+    B: I2CBus + ?Sized,
+    TransactionError<B>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Transaction(x) => f.debug_tuple("Transaction").field(x).finish(),
+            Self::InvalidUtf8 => f.write_str("InvalidUtf8"),
+        }
+    }
+}
+
+/// Represents an error of [`I2CBus::read_stable`].
+pub enum StableReadError<B: I2CBus + ?Sized> {
+    Transaction(TransactionError<B>),
+    Unstable,
+}
+
+impl<B> fmt::Debug for StableReadError<B>
+where
+    B: I2CBus + ?Sized,
+    TransactionError<B>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Transaction(x) => f.debug_tuple("Transaction").field(x).finish(),
+            Self::Unstable => f.write_str("Unstable"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use core::cell::RefCell;
+    use std::vec;
+    use std::vec::Vec;
+
+    /// Represents a call recorded by [`MockBus`]
+    #[derive(Debug, PartialEq)]
+    enum Call {
+        Start,
+        Stop,
+        Header(u8, Direction),
+        Send(Vec<u8>),
+        Read(usize, bool),
+    }
+
+    /// Represents a bus recording its calls, supporting
+    /// repeated starts if `REPEATED_START` is set
+    struct MockBus<const REPEATED_START: bool> {
+        calls: RefCell<Vec<Call>>,
+        nack_address: Option<u8>,
+        outcomes: RefCell<Vec<bool>>,
+    }
+
+    impl<const REPEATED_START: bool> MockBus<REPEATED_START> {
+        /// Creates a bus on which every slave acknowledges
+        fn new() -> Self {
+            Self {
+                calls: RefCell::new(Vec::new()),
+                nack_address: None,
+                outcomes: RefCell::new(Vec::new()),
+            }
+        }
+
+        /// Creates a bus on which the slave at `address` does not acknowledge
+        fn nacking(address: u8) -> Self {
+            Self {
+                nack_address: Some(address),
+                ..Self::new()
+            }
+        }
+
+        /// Returns the calls recorded so far, clearing them
+        fn calls(&self) -> Vec<Call> {
+            self.calls.take()
+        }
+
+        fn record(&self, call: Call) {
+            self.calls.borrow_mut().push(call);
+        }
+
+        /// Returns whether each transaction observed so far succeeded,
+        /// clearing them
+        fn outcomes(&self) -> Vec<bool> {
+            self.outcomes.take()
+        }
+    }
+
+    impl<const REPEATED_START: bool> I2CBus for MockBus<REPEATED_START> {
+        type StartConditionError = ();
+        type StopCondidionError = ();
+        type SendHeaderError = ();
+        type SendError = ();
+        type ReadError = ();
+
+        const SUPPORTS_REPEATED_START: bool = REPEATED_START;
+
+        fn init(&self) {}
+
+        fn start_condition(&self) -> Result<(), ()> {
+            self.record(Call::Start);
+            Ok(())
+        }
+
+        fn stop_condition(&self) -> Result<(), ()> {
+            self.record(Call::Stop);
+            Ok(())
+        }
+
+        fn send_header(
+            &self,
+            address: impl Into<SevenBitAddress>,
+            direction: Direction,
+        ) -> Result<(), ()> {
+            let address = address.into().get();
+            self.record(Call::Header(address, direction));
+            match self.nack_address {
+                Some(nack_address) if nack_address == address => Err(()),
+                _ => Ok(()),
+            }
+        }
+
+        fn send(&self, data: &[u8]) -> Result<(), ()> {
+            self.record(Call::Send(data.to_vec()));
+            Ok(())
+        }
+
+        fn read(&self, data: &mut [u8], end_with_nack: bool) -> Result<(), ()> {
+            self.record(Call::Read(data.len(), end_with_nack));
+            for (index, byte) in data.iter_mut().enumerate() {
+                *byte = index as u8;
+            }
+            Ok(())
+        }
+
+        fn after_transaction(
+            &self,
+            result: Result<(), TransactionError<Self>>,
+        ) -> Result<(), TransactionError<Self>> {
+            self.outcomes.borrow_mut().push(result.is_ok());
+            result
+        }
+    }
+
+    #[test]
+    fn separator_keeps_the_bus_for_the_same_device() {
+        assert_eq!(separator(0x50, 0x50, true), Separator::RepeatedStart);
+    }
+
+    #[test]
+    fn separator_stops_without_repeated_start_support() {
+        assert_eq!(separator(0x50, 0x50, false), Separator::StopAndStart);
+    }
+
+    #[test]
+    fn separator_stops_between_devices() {
+        assert_eq!(separator(0x50, 0x51, true), Separator::StopAndStart);
+        assert_eq!(separator(0x50, 0x51, false), Separator::StopAndStart);
+    }
+
+    #[test]
+    fn transaction_uses_a_repeated_start_on_the_same_device() {
+        let bus = MockBus::<true>::new();
+        let mut read = [0; 2];
+        bus.transaction(&mut [
+            Operation::Write {
+                address: 0x50,
+                data: &[0x10],
+            },
+            Operation::Read {
+                address: 0x50,
+                data: &mut read,
+            },
+        ])
+        .unwrap();
+        assert_eq!(
+            bus.calls(),
+            [
+                Call::Start,
+                Call::Header(0x50, Direction::Write),
+                Call::Send(vec![0x10]),
+                Call::Start,
+                Call::Header(0x50, Direction::Read),
+                Call::Read(2, true),
+                Call::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn transaction_stops_on_the_same_device_without_repeated_start_support() {
+        let bus = MockBus::<false>::new();
+        bus.transaction(&mut [
+            Operation::Write {
+                address: 0x50,
+                data: &[0x10],
+            },
+            Operation::Write {
+                address: 0x50,
+                data: &[0x20],
+            },
+        ])
+        .unwrap();
+        assert_eq!(
+            bus.calls(),
+            [
+                Call::Start,
+                Call::Header(0x50, Direction::Write),
+                Call::Send(vec![0x10]),
+                Call::Stop,
+                Call::Start,
+                Call::Header(0x50, Direction::Write),
+                Call::Send(vec![0x20]),
+                Call::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn transaction_stops_between_devices() {
+        let bus = MockBus::<true>::new();
+        bus.transaction(&mut [
+            Operation::Write {
+                address: 0x50,
+                data: &[0x10],
+            },
+            Operation::Write {
+                address: 0x51,
+                data: &[0x20],
+            },
+        ])
+        .unwrap();
+        assert_eq!(
+            bus.calls(),
+            [
+                Call::Start,
+                Call::Header(0x50, Direction::Write),
+                Call::Send(vec![0x10]),
+                Call::Stop,
+                Call::Start,
+                Call::Header(0x51, Direction::Write),
+                Call::Send(vec![0x20]),
+                Call::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn transaction_stops_after_an_error() {
+        let bus = MockBus::<true>::nacking(0x51);
+        let result = bus.transaction(&mut [
+            Operation::Write {
+                address: 0x51,
+                data: &[0x10],
+            },
+            Operation::Write {
+                address: 0x50,
+                data: &[0x20],
+            },
+        ]);
+        assert!(matches!(result, Err(TransactionError::SendHeader(()))));
+        assert_eq!(
+            bus.calls(),
+            [
+                Call::Start,
+                Call::Header(0x51, Direction::Write),
+                Call::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn write_read_with_an_empty_read_only_writes() {
+        let bus = MockBus::<true>::new();
+        assert!(bus.write_read(0x50, &[0x10], &mut []).is_ok());
+        assert_eq!(
+            bus.calls(),
+            [
+                Call::Start,
+                Call::Header(0x50, Direction::Write),
+                Call::Send(vec![0x10]),
+                Call::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn read_cstr_addresses_the_register_then_reads_after_a_repeated_start() {
+        let bus = MockBus::<true>::new();
+        assert_eq!(bus.read_cstr(0x50, 0x10, &mut [0; 4]).ok(), Some(""));
+        assert_eq!(
+            bus.calls(),
+            [
+                Call::Start,
+                Call::Header(0x50, Direction::Write),
+                Call::Send(vec![0x10]),
+                Call::Start,
+                Call::Header(0x50, Direction::Read),
+                Call::Read(1, false),
+                Call::Read(1, true),
+                Call::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn read_from_into_an_empty_buffer_addresses_the_slave_for_writing() {
+        let bus = MockBus::<true>::new();
+        assert!(bus.read_from(0x50, &mut []).is_ok());
+        assert_eq!(
+            bus.calls(),
+            [
+                Call::Start,
+                Call::Header(0x50, Direction::Write),
+                Call::Send(vec![]),
+                Call::Stop,
+            ]
+        );
+        assert_eq!(bus.outcomes(), [true]);
+    }
+
+    #[test]
+    fn read_from_into_an_empty_buffer_reports_a_missing_slave() {
+        let bus = MockBus::<true>::nacking(0x50);
+        assert!(matches!(
+            bus.read_from(0x50, &mut []),
+            Err(TransactionError::SendHeader(()))
+        ));
+        assert_eq!(
+            bus.calls(),
+            [
+                Call::Start,
+                Call::Header(0x50, Direction::Write),
+                Call::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn transaction_addresses_an_empty_read_for_writing() {
+        let bus = MockBus::<true>::new();
+        let mut operations = [Operation::Read {
+            address: 0x50,
+            data: &mut [],
+        }];
+        assert!(bus.transaction(&mut operations).is_ok());
+        assert_eq!(
+            bus.calls(),
+            [
+                Call::Start,
+                Call::Header(0x50, Direction::Write),
+                Call::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn read_cstr_into_an_empty_buffer_issues_no_bus_calls() {
+        let bus = MockBus::<true>::new();
+        assert_eq!(bus.read_cstr(0x50, 0x10, &mut []).ok(), Some(""));
+        assert_eq!(bus.calls(), []);
+    }
+
+    fn register_write(value: [u8; 4], len: usize) -> [Call; 5] {
+        [
+            Call::Start,
+            Call::Header(0x50, Direction::Write),
+            Call::Send(vec![0x10]),
+            Call::Send(value[..len].to_vec()),
+            Call::Stop,
+        ]
+    }
+
+    #[test]
+    fn write_u16_sends_the_value_in_the_requested_byte_order() {
+        let bus = MockBus::<true>::new();
+        assert!(bus.write_u16_le(0x50, 0x10, 0x1234).is_ok());
+        assert_eq!(bus.calls(), register_write([0x34, 0x12, 0, 0], 2));
+        assert!(bus.write_u16_be(0x50, 0x10, 0x1234).is_ok());
+        assert_eq!(bus.calls(), register_write([0x12, 0x34, 0, 0], 2));
+    }
+
+    #[test]
+    fn write_u32_sends_the_value_in_the_requested_byte_order() {
+        let bus = MockBus::<true>::new();
+        assert!(bus.write_u32_le(0x50, 0x10, 0x1234_5678).is_ok());
+        assert_eq!(bus.calls(), register_write([0x78, 0x56, 0x34, 0x12], 4));
+        assert!(bus.write_u32_be(0x50, 0x10, 0x1234_5678).is_ok());
+        assert_eq!(bus.calls(), register_write([0x12, 0x34, 0x56, 0x78], 4));
+    }
+
+    fn next_address(address: SevenBitAddress) -> SevenBitAddress {
+        SevenBitAddress::new(address.get() + 1).unwrap()
+    }
+
+    #[test]
+    fn translated_sends_headers_to_the_translated_address() {
+        let bus = MockBus::<true>::new();
+        let translated = Translated::new(&bus, next_address);
+        assert!(translated.write_to(0x50, &[0x10]).is_ok());
+        assert_eq!(bus.calls()[1], Call::Header(0x51, Direction::Write));
+    }
+
+    #[test]
+    fn translated_forwards_after_transaction() {
+        let bus = MockBus::<true>::nacking(0x51);
+        let translated = Translated::new(&bus, next_address);
+        assert!(translated.write_to(0x4F, &[0x10]).is_ok());
+        let result = translated.write_to(0x50, &[0x10]);
+        assert!(matches!(result, Err(TransactionError::SendHeader(()))));
+        assert_eq!(bus.outcomes(), [true, false]);
+    }
+
+    #[test]
+    fn helpers_accept_seven_bit_addresses() {
+        let bus = MockBus::<true>::new();
+        let address = SevenBitAddress::new(0x50).unwrap();
+        assert!(bus.write_to(address, &[0x10]).is_ok());
+        assert!(bus.write_to(0x50, &[0x10]).is_ok());
+        assert_eq!(bus.calls()[1], Call::Header(0x50, Direction::Write));
+    }
+
+    #[test]
+    fn helpers_reject_out_of_range_addresses() {
+        let bus = MockBus::<true>::new();
+        let result = bus.write_to(0x80, &[0x10]);
+        assert!(matches!(result, Err(TransactionError::InvalidAddress)));
+        assert!(!bus.probe(0xFF));
+        assert_eq!(bus.calls(), []);
+    }
+
+    #[test]
+    fn transaction_rejects_out_of_range_addresses_before_the_start() {
+        let bus = MockBus::<true>::new();
+        let result = bus.transaction(&mut [
+            Operation::Write {
+                address: 0x50,
+                data: &[0x10],
+            },
+            Operation::Write {
+                address: 0xA0,
+                data: &[0x20],
+            },
+        ]);
+        assert!(matches!(result, Err(TransactionError::InvalidAddress)));
+        assert_eq!(bus.calls(), []);
+    }
+
+    #[test]
+    fn read_register_at_rejects_offsets_leaving_the_address_range() {
+        let bus = MockBus::<true>::new();
+        let result = bus.read_register_at(0x7F, 1, 0x10, &mut [0]);
+        assert!(matches!(result, Err(TransactionError::InvalidAddress)));
+        let result = bus.read_register_at(0x7F, 0xFF, 0x10, &mut [0]);
+        assert!(matches!(result, Err(TransactionError::InvalidAddress)));
+        assert_eq!(bus.calls(), []);
+        assert!(bus.read_register_at(0x48, 3, 0x10, &mut [0]).is_ok());
+        assert_eq!(bus.calls()[1], Call::Header(0x4B, Direction::Write));
+    }
+
+    #[test]
+    fn seven_bit_address_conversion_rejects_out_of_range_values() {
+        assert_eq!(
+            SevenBitAddress::try_from(0x7F).map(SevenBitAddress::get),
+            Ok(0x7F)
+        );
+        assert_eq!(
+            SevenBitAddress::try_from(0x80),
+            Err(AddressOutOfRange(0x80))
+        );
+    }
 }
@@ -0,0 +1,499 @@
+//! Data-driven transaction plans for automated bus testing.
+//!
+//! A plan is a sequence of low-level [`Step`]s, parsed from a compact
+//! binary encoding so test vectors can live outside the code. Each step
+//! starts with an opcode byte:
+//!
+//! | Opcode | Step     | Operands                                        |
+//! |--------|----------|-------------------------------------------------|
+//! | `0x01` | `Start`  |                                                 |
+//! | `0x02` | `Stop`   |                                                 |
+//! | `0x03` | `Header` | address, direction (`0` write, `1` read)        |
+//! | `0x04` | `Write`  | length, then `length` data bytes                |
+//! | `0x05` | `Read`   | NACK flag (`0`/`1`), length, then `length` expected bytes |
+
+use core::fmt;
+
+use crate::{Direction, I2CBus, SevenBitAddress, TransactionError};
+
+/// Represents a single step of a [`TransactionPlan`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step<'a> {
+    Start,
+    Stop,
+    Header {
+        address: u8,
+        direction: Direction,
+    },
+    Write(&'a [u8]),
+    Read {
+        expected: &'a [u8],
+        end_with_nack: bool,
+    },
+}
+
+/// Represents a sequence of at most `N` [`Step`]s.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionPlan<'a, const N: usize> {
+    steps: [Step<'a>; N],
+    len: usize,
+}
+
+/// Represents an error parsing an encoded [`TransactionPlan`].
+///
+/// `offset` is the position of the offending step's opcode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParseError {
+    UnknownOpcode { offset: usize },
+    InvalidOperand { offset: usize },
+    Truncated { offset: usize },
+    TooManySteps,
+}
+
+/// Represents a structural problem of a [`TransactionPlan`],
+/// found by [`TransactionPlan::validate`].
+///
+/// `step` is the index of the offending step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlanError {
+    /// A header address does not fit in seven bits
+    AddressOutOfRange { step: usize },
+    /// A read step reads no bytes
+    EmptyRead { step: usize },
+    /// A step other than `Start` occurs outside a transaction,
+    /// or a header does not follow a (repeated) start
+    MissingStart { step: usize },
+    /// A (repeated) start is not followed by a header
+    MissingHeader { step: usize },
+    /// A data step does not match the direction of the header
+    DirectionMismatch { step: usize },
+    /// A read step follows a read that already ended with a NACK
+    ReadAfterNack { step: usize },
+    /// A stop follows a read that did not end with a NACK,
+    /// leaving the slave driving SDA
+    MissingNack { step: usize },
+    /// The plan ends in the middle of a transaction
+    MissingStop,
+}
+
+/// Represents where a plan is within a transaction, for validation.
+#[derive(Clone, Copy, PartialEq)]
+enum PlanState {
+    Idle,
+    Started,
+    Writing,
+    Reading { nacked: bool },
+}
+
+/// Represents a read byte that did not match its expected value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mismatch {
+    pub step: usize,
+    pub index: usize,
+    pub expected: u8,
+    pub actual: u8,
+}
+
+/// Represents the outcome of an executed [`TransactionPlan`].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Report {
+    /// The number of read bytes that did not match their expected value
+    pub mismatches: usize,
+    /// The first read byte that did not match its expected value
+    pub first_mismatch: Option<Mismatch>,
+}
+
+/// Represents a bus error that stopped the execution of a [`TransactionPlan`].
+pub struct ExecuteError<B: I2CBus + ?Sized> {
+    /// The index of the failed step
+    pub step: usize,
+    pub error: TransactionError<B>,
+}
+
+impl<B> fmt::Debug for ExecuteError<B>
+where
+    B: I2CBus + ?Sized,
+    TransactionError<B>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExecuteError")
+            .field("step", &self.step)
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+impl<'a, const N: usize> TransactionPlan<'a, N> {
+    /// Parses an encoded plan.
+    ///
+    /// The data of `Write` and `Read` steps borrows from `encoded`.
+    pub fn parse(encoded: &'a [u8]) -> Result<Self, ParseError> {
+        let mut plan = Self {
+            steps: [Step::Start; N],
+            len: 0,
+        };
+        let mut offset = 0;
+        while offset < encoded.len() {
+            let operands = &encoded[offset + 1..];
+            let truncated = ParseError::Truncated { offset };
+            let (step, size) = match encoded[offset] {
+                0x01 => (Step::Start, 0),
+                0x02 => (Step::Stop, 0),
+                0x03 => {
+                    let &[address, direction, ..] = operands else {
+                        return Err(truncated);
+                    };
+                    let direction = match direction {
+                        0 => Direction::Write,
+                        1 => Direction::Read,
+                        _ => return Err(ParseError::InvalidOperand { offset }),
+                    };
+                    (Step::Header { address, direction }, 2)
+                }
+                0x04 => {
+                    let len = *operands.first().ok_or(truncated)? as usize;
+                    let data = operands.get(1..1 + len).ok_or(truncated)?;
+                    (Step::Write(data), 1 + len)
+                }
+                0x05 => {
+                    let &[nack, len, ..] = operands else {
+                        return Err(truncated);
+                    };
+                    let end_with_nack = match nack {
+                        0 => false,
+                        1 => true,
+                        _ => return Err(ParseError::InvalidOperand { offset }),
+                    };
+                    let expected = operands.get(2..2 + len as usize).ok_or(truncated)?;
+                    let step = Step::Read {
+                        expected,
+                        end_with_nack,
+                    };
+                    (step, 2 + len as usize)
+                }
+                _ => return Err(ParseError::UnknownOpcode { offset }),
+            };
+            *plan
+                .steps
+                .get_mut(plan.len)
+                .ok_or(ParseError::TooManySteps)? = step;
+            plan.len += 1;
+            offset += 1 + size;
+        }
+        Ok(plan)
+    }
+
+    /// Returns the steps of the plan.
+    pub fn steps(&self) -> &[Step<'a>] {
+        &self.steps[..self.len]
+    }
+
+    /// Checks the structure of the plan without any bus activity.
+    ///
+    /// A valid plan consists of transactions that each begin with a
+    /// start followed by a header, only contain data steps matching the
+    /// direction of the latest header, end reads with a NACK before the
+    /// stop, and end with a stop. Header addresses must fit in seven
+    /// bits, and reads must read at least one byte.
+    pub fn validate(&self) -> Result<(), PlanError> {
+        let mut state = PlanState::Idle;
+        for (step, &operation) in self.steps().iter().enumerate() {
+            state = match (operation, state) {
+                (Step::Start, _) => PlanState::Started,
+                (_, PlanState::Idle) => return Err(PlanError::MissingStart { step }),
+                (Step::Header { address, .. }, _) if address > 0x7F => {
+                    return Err(PlanError::AddressOutOfRange { step })
+                }
+                (Step::Header { direction, .. }, PlanState::Started) => match direction {
+                    Direction::Write => PlanState::Writing,
+                    Direction::Read => PlanState::Reading { nacked: false },
+                },
+                (Step::Header { .. }, _) => return Err(PlanError::MissingStart { step }),
+                (_, PlanState::Started) => return Err(PlanError::MissingHeader { step }),
+                (Step::Stop, PlanState::Reading { nacked: false }) => {
+                    return Err(PlanError::MissingNack { step })
+                }
+                (Step::Stop, _) => PlanState::Idle,
+                (Step::Write(_), PlanState::Writing) => PlanState::Writing,
+                (Step::Read { expected: [], .. }, _) => return Err(PlanError::EmptyRead { step }),
+                (Step::Read { end_with_nack, .. }, PlanState::Reading { nacked: false }) => {
+                    PlanState::Reading {
+                        nacked: end_with_nack,
+                    }
+                }
+                (Step::Read { .. }, PlanState::Reading { nacked: true }) => {
+                    return Err(PlanError::ReadAfterNack { step })
+                }
+                (Step::Write(_) | Step::Read { .. }, _) => {
+                    return Err(PlanError::DirectionMismatch { step })
+                }
+            };
+        }
+        match state {
+            PlanState::Idle => Ok(()),
+            _ => Err(PlanError::MissingStop),
+        }
+    }
+
+    /// Executes the plan on `bus`, comparing read bytes against
+    /// their expected values.
+    ///
+    /// Mismatching bytes do not stop the execution, and are counted in
+    /// the returned [`Report`]. A bus error stops the execution as is:
+    /// no stop condition is issued on behalf of the plan. So does a
+    /// header address that does not fit in seven bits, which fails
+    /// its step with [`TransactionError::InvalidAddress`] without
+    /// sending the header; [`validate`] reports such plans before
+    /// any bus activity.
+    ///
+    /// [`validate`]: Self::validate
+    pub fn execute<B: I2CBus>(&self, bus: &B) -> Result<Report, ExecuteError<B>> {
+        let mut report = Report::default();
+        for (step, &operation) in self.steps().iter().enumerate() {
+            let fail = |error| ExecuteError { step, error };
+            match operation {
+                Step::Start => bus
+                    .start_condition()
+                    .map_err(|x| fail(TransactionError::StartCondition(x)))?,
+                Step::Stop => bus
+                    .stop_condition()
+                    .map_err(|x| fail(TransactionError::StopCondition(x)))?,
+                Step::Header { address, direction } => {
+                    let address = SevenBitAddress::new(address)
+                        .ok_or_else(|| fail(TransactionError::InvalidAddress))?;
+                    bus.send_header(address, direction)
+                        .map_err(|x| fail(TransactionError::SendHeader(x)))?
+                }
+                Step::Write(data) => bus
+                    .send(data)
+                    .map_err(|x| fail(TransactionError::Send(x)))?,
+                Step::Read {
+                    expected,
+                    end_with_nack,
+                } => {
+                    for (index, &byte) in expected.iter().enumerate() {
+                        let mut actual = [0];
+                        let last = index + 1 == expected.len();
+                        bus.read(&mut actual, end_with_nack && last)
+                            .map_err(|x| fail(TransactionError::Read(x)))?;
+                        if actual[0] != byte {
+                            report.mismatches += 1;
+                            report.first_mismatch.get_or_insert(Mismatch {
+                                step,
+                                index,
+                                expected: byte,
+                                actual: actual[0],
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+
+    /// Represents a bus on which every operation succeeds,
+    /// counting the headers sent
+    struct CountingBus {
+        headers: Cell<usize>,
+    }
+
+    impl I2CBus for CountingBus {
+        type StartConditionError = ();
+        type StopCondidionError = ();
+        type SendHeaderError = ();
+        type SendError = ();
+        type ReadError = ();
+
+        fn init(&self) {}
+
+        fn start_condition(&self) -> Result<(), ()> {
+            Ok(())
+        }
+
+        fn stop_condition(&self) -> Result<(), ()> {
+            Ok(())
+        }
+
+        fn send_header(
+            &self,
+            _address: impl Into<SevenBitAddress>,
+            _direction: Direction,
+        ) -> Result<(), ()> {
+            self.headers.set(self.headers.get() + 1);
+            Ok(())
+        }
+
+        fn send(&self, _data: &[u8]) -> Result<(), ()> {
+            Ok(())
+        }
+
+        fn read(&self, data: &mut [u8], _end_with_nack: bool) -> Result<(), ()> {
+            data.fill(0);
+            Ok(())
+        }
+    }
+
+    fn parse(encoded: &[u8]) -> Result<(), ParseError> {
+        TransactionPlan::<4>::parse(encoded).map(|_| ())
+    }
+
+    fn validate(encoded: &[u8]) -> Result<(), PlanError> {
+        TransactionPlan::<8>::parse(encoded).unwrap().validate()
+    }
+
+    #[test]
+    fn parse_rejects_unknown_opcodes() {
+        assert_eq!(
+            parse(&[0x01, 0x07]),
+            Err(ParseError::UnknownOpcode { offset: 1 })
+        );
+    }
+
+    #[test]
+    fn parse_rejects_invalid_operands() {
+        let invalid = Err(ParseError::InvalidOperand { offset: 1 });
+        assert_eq!(parse(&[0x01, 0x03, 0x50, 0x02]), invalid);
+        assert_eq!(parse(&[0x01, 0x05, 0x02, 0x01, 0xAA]), invalid);
+    }
+
+    #[test]
+    fn parse_rejects_truncated_steps() {
+        let truncated = Err(ParseError::Truncated { offset: 1 });
+        assert_eq!(parse(&[0x01, 0x03, 0x50]), truncated);
+        assert_eq!(parse(&[0x01, 0x04]), truncated);
+        assert_eq!(parse(&[0x01, 0x04, 0x02, 0xAA]), truncated);
+        assert_eq!(parse(&[0x01, 0x05, 0x01]), truncated);
+        assert_eq!(parse(&[0x01, 0x05, 0x01, 0x02, 0xAA]), truncated);
+    }
+
+    #[test]
+    fn parse_rejects_too_many_steps() {
+        assert_eq!(parse(&[0x01; 4]), Ok(()));
+        assert_eq!(parse(&[0x01; 5]), Err(ParseError::TooManySteps));
+    }
+
+    #[test]
+    fn validate_accepts_a_write_then_read() {
+        let plan = [
+            0x01, 0x03, 0x50, 0x00, 0x04, 0x01, 0x10, 0x01, 0x03, 0x50, 0x01, 0x05, 0x01, 0x01,
+            0xAA, 0x02,
+        ];
+        assert_eq!(validate(&plan), Ok(()));
+    }
+
+    #[test]
+    fn validate_rejects_out_of_range_addresses() {
+        assert_eq!(
+            validate(&[0x01, 0x03, 0x80, 0x00, 0x02]),
+            Err(PlanError::AddressOutOfRange { step: 1 })
+        );
+    }
+
+    #[test]
+    fn validate_rejects_empty_reads() {
+        assert_eq!(
+            validate(&[0x01, 0x03, 0x50, 0x01, 0x05, 0x01, 0x00, 0x02]),
+            Err(PlanError::EmptyRead { step: 2 })
+        );
+    }
+
+    #[test]
+    fn validate_rejects_steps_outside_a_transaction() {
+        assert_eq!(validate(&[0x02]), Err(PlanError::MissingStart { step: 0 }));
+        assert_eq!(
+            validate(&[0x01, 0x03, 0x50, 0x00, 0x03, 0x50, 0x00, 0x02]),
+            Err(PlanError::MissingStart { step: 2 })
+        );
+    }
+
+    #[test]
+    fn validate_rejects_starts_without_a_header() {
+        assert_eq!(
+            validate(&[0x01, 0x04, 0x01, 0x10, 0x02]),
+            Err(PlanError::MissingHeader { step: 1 })
+        );
+    }
+
+    #[test]
+    fn validate_rejects_data_against_the_header_direction() {
+        assert_eq!(
+            validate(&[0x01, 0x03, 0x50, 0x00, 0x05, 0x01, 0x01, 0xAA, 0x02]),
+            Err(PlanError::DirectionMismatch { step: 2 })
+        );
+        assert_eq!(
+            validate(&[0x01, 0x03, 0x50, 0x01, 0x04, 0x01, 0x10, 0x02]),
+            Err(PlanError::DirectionMismatch { step: 2 })
+        );
+    }
+
+    #[test]
+    fn validate_rejects_reads_after_a_nack() {
+        let plan = [
+            0x01, 0x03, 0x50, 0x01, 0x05, 0x01, 0x01, 0xAA, 0x05, 0x01, 0x01, 0xBB, 0x02,
+        ];
+        assert_eq!(validate(&plan), Err(PlanError::ReadAfterNack { step: 3 }));
+    }
+
+    #[test]
+    fn validate_rejects_stops_after_an_acked_read() {
+        assert_eq!(
+            validate(&[0x01, 0x03, 0x50, 0x01, 0x05, 0x00, 0x01, 0xAA, 0x02]),
+            Err(PlanError::MissingNack { step: 3 })
+        );
+    }
+
+    #[test]
+    fn validate_rejects_unterminated_transactions() {
+        assert_eq!(
+            validate(&[0x01, 0x03, 0x50, 0x00]),
+            Err(PlanError::MissingStop)
+        );
+    }
+
+    #[test]
+    fn execute_fails_on_out_of_range_addresses_without_sending_them() {
+        let bus = CountingBus {
+            headers: Cell::new(0),
+        };
+        let plan = TransactionPlan::<8>::parse(&[0x01, 0x03, 0x80, 0x00, 0x02]).unwrap();
+        let result = plan.execute(&bus);
+        assert!(matches!(
+            result,
+            Err(ExecuteError {
+                step: 1,
+                error: TransactionError::InvalidAddress,
+            })
+        ));
+        assert_eq!(bus.headers.get(), 0);
+    }
+
+    #[test]
+    fn execute_reports_mismatching_bytes() {
+        let bus = CountingBus {
+            headers: Cell::new(0),
+        };
+        let plan = [0x01, 0x03, 0x50, 0x01, 0x05, 0x01, 0x02, 0x00, 0xAA, 0x02];
+        let report = TransactionPlan::<8>::parse(&plan)
+            .unwrap()
+            .execute(&bus)
+            .unwrap();
+        assert_eq!(report.mismatches, 1);
+        assert_eq!(
+            report.first_mismatch,
+            Some(Mismatch {
+                step: 2,
+                index: 1,
+                expected: 0xAA,
+                actual: 0,
+            })
+        );
+        assert_eq!(bus.headers.get(), 1);
+    }
+}
@@ -0,0 +1,341 @@
+//! A register map abstraction for I2C devices.
+
+use core::marker::PhantomData;
+
+use crate::{I2CBus, TransactionError};
+
+/// Represents the registers of a single device on an [`I2CBus`].
+///
+/// Device drivers can wrap a [`RegisterMap`] instead of
+/// implementing register access themselves.
+pub struct RegisterMap<'a, B: I2CBus> {
+    bus: &'a B,
+    address: u8,
+}
+
+impl<'a, B: I2CBus> RegisterMap<'a, B> {
+    /// Creates a new register map for the device at `address`
+    pub const fn new(bus: &'a B, address: u8) -> Self {
+        Self { bus, address }
+    }
+
+    /// Returns the address of the device
+    pub const fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// Returns the bus the device is on
+    pub const fn bus(&self) -> &'a B {
+        self.bus
+    }
+
+    /// Reads a single register
+    pub fn read_reg(&self, register: u8) -> Result<u8, TransactionError<B>> {
+        let mut value = [0];
+        self.bus.read_register(self.address, register, &mut value)?;
+        Ok(value[0])
+    }
+
+    /// Writes a single register
+    pub fn write_reg(&self, register: u8, value: u8) -> Result<(), TransactionError<B>> {
+        self.bus.write_register(self.address, register, &[value])
+    }
+
+    /// Reads a register, transforms its value with `f`,
+    /// then writes the result back.
+    ///
+    /// The read and the write are separate transactions.
+    pub fn modify_reg(
+        &self,
+        register: u8,
+        f: impl FnOnce(u8) -> u8,
+    ) -> Result<(), TransactionError<B>> {
+        let value = self.read_reg(register)?;
+        self.write_reg(register, f(value))
+    }
+
+    /// Reads consecutive registers starting at `register`
+    ///
+    /// This relies on the device auto-incrementing its
+    /// register pointer.
+    pub fn read_regs(&self, register: u8, data: &mut [u8]) -> Result<(), TransactionError<B>> {
+        self.bus.read_register(self.address, register, data)
+    }
+}
+
+/// Represents a value stored in one or more consecutive registers.
+pub trait RegisterValue: Sized {
+    /// The raw bytes of the value, as stored in the registers.
+    type Bytes: AsRef<[u8]> + AsMut<[u8]> + Default;
+
+    /// Creates a value from its big-endian bytes.
+    fn from_be_bytes(bytes: Self::Bytes) -> Self;
+
+    /// Creates a value from its little-endian bytes.
+    fn from_le_bytes(bytes: Self::Bytes) -> Self;
+
+    /// Returns the big-endian bytes of the value.
+    fn to_be_bytes(self) -> Self::Bytes;
+
+    /// Returns the little-endian bytes of the value.
+    fn to_le_bytes(self) -> Self::Bytes;
+}
+
+macro_rules! impl_register_value {
+    ($($ty:ty),*) => {
+        $(
+            impl RegisterValue for $ty {
+                type Bytes = [u8; core::mem::size_of::<$ty>()];
+
+                fn from_be_bytes(bytes: Self::Bytes) -> Self {
+                    <$ty>::from_be_bytes(bytes)
+                }
+
+                fn from_le_bytes(bytes: Self::Bytes) -> Self {
+                    <$ty>::from_le_bytes(bytes)
+                }
+
+                fn to_be_bytes(self) -> Self::Bytes {
+                    <$ty>::to_be_bytes(self)
+                }
+
+                fn to_le_bytes(self) -> Self::Bytes {
+                    <$ty>::to_le_bytes(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_register_value!(u8, i8, u16, i16, u32, i32);
+
+/// Represents the order in which a multi-byte value is stored
+/// in consecutive registers.
+pub trait ByteOrder {
+    /// Decodes a value from the bytes read from the registers.
+    fn decode<V: RegisterValue>(bytes: V::Bytes) -> V;
+
+    /// Encodes a value into the bytes written to the registers.
+    fn encode<V: RegisterValue>(value: V) -> V::Bytes;
+}
+
+/// The most significant byte is stored in the first register.
+pub enum BigEndian {}
+
+impl ByteOrder for BigEndian {
+    fn decode<V: RegisterValue>(bytes: V::Bytes) -> V {
+        V::from_be_bytes(bytes)
+    }
+
+    fn encode<V: RegisterValue>(value: V) -> V::Bytes {
+        value.to_be_bytes()
+    }
+}
+
+/// The least significant byte is stored in the first register.
+pub enum LittleEndian {}
+
+impl ByteOrder for LittleEndian {
+    fn decode<V: RegisterValue>(bytes: V::Bytes) -> V {
+        V::from_le_bytes(bytes)
+    }
+
+    fn encode<V: RegisterValue>(value: V) -> V::Bytes {
+        value.to_le_bytes()
+    }
+}
+
+/// Marks an access mode that allows reading a [`Register`].
+pub trait Readable {}
+
+/// Marks an access mode that allows writing a [`Register`].
+pub trait Writable {}
+
+/// The register can only be read.
+pub enum ReadOnly {}
+
+impl Readable for ReadOnly {}
+
+/// The register can only be written.
+pub enum WriteOnly {}
+
+impl Writable for WriteOnly {}
+
+/// The register can be both read and written.
+pub enum ReadWrite {}
+
+impl Readable for ReadWrite {}
+
+impl Writable for ReadWrite {}
+
+/// Represents a typed register of a device on an [`I2CBus`].
+///
+/// `V` is the type of the value, `O` its [`ByteOrder`] and `A`
+/// the access mode, which decides whether the register can be
+/// read, written, or both. Registers are usually obtained from
+/// a driver generated by [`i2c_device!`].
+///
+/// [`i2c_device!`]: crate::i2c_device
+pub struct Register<'a, B: I2CBus, V, O, A> {
+    map: RegisterMap<'a, B>,
+    register: u8,
+    marker: PhantomData<(V, O, A)>,
+}
+
+impl<'a, B: I2CBus, V, O, A> Register<'a, B, V, O, A> {
+    /// Creates a new register at `register` of the device of `map`
+    pub const fn new(map: RegisterMap<'a, B>, register: u8) -> Self {
+        Self {
+            map,
+            register,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the address of the (first) register
+    pub const fn register(&self) -> u8 {
+        self.register
+    }
+}
+
+impl<B: I2CBus, V: RegisterValue, O: ByteOrder, A: Readable> Register<'_, B, V, O, A> {
+    /// Reads the value of the register
+    pub fn read(&self) -> Result<V, TransactionError<B>> {
+        let mut bytes = V::Bytes::default();
+        self.map.read_regs(self.register, bytes.as_mut())?;
+        Ok(O::decode(bytes))
+    }
+}
+
+impl<B: I2CBus, V: RegisterValue, O: ByteOrder, A: Writable> Register<'_, B, V, O, A> {
+    /// Writes the value of the register
+    pub fn write(&self, value: V) -> Result<(), TransactionError<B>> {
+        self.map
+            .bus()
+            .write_register(self.map.address(), self.register, O::encode(value).as_ref())
+    }
+}
+
+impl<B: I2CBus, V: RegisterValue, O: ByteOrder, A: Readable + Writable> Register<'_, B, V, O, A> {
+    /// Reads the register, transforms its value with `f`,
+    /// then writes the result back.
+    ///
+    /// The read and the write are separate transactions.
+    pub fn modify(&self, f: impl FnOnce(V) -> V) -> Result<(), TransactionError<B>> {
+        let value = self.read()?;
+        self.write(f(value))
+    }
+}
+
+/// Defines a typed driver for a register-mapped device.
+///
+/// The driver wraps a [`RegisterMap`] and has one method per
+/// register, returning a [`Register`] to read or write it. Each
+/// register is declared as `name: type @ register`, where `type`
+/// is an integer type implementing [`RegisterValue`], followed
+/// by optional annotations:
+///
+/// - `be` or `le` selects the [`ByteOrder`] of multi-byte values,
+///   big-endian by default;
+/// - `r`, `w` or `rw` selects the access mode, read-write by default.
+///
+/// ```
+/// use avr_i2c::{i2c_device, I2CBus, TransactionError};
+///
+/// i2c_device! {
+///     /// A BME280 sensor
+///     pub Bme280 at 0x76 {
+///         /// The raw temperature
+///         temp: u16 @ 0xFA be r,
+///         /// The measurement control register
+///         ctrl: u8 @ 0xF4 rw,
+///     }
+/// }
+///
+/// fn start<B: I2CBus>(bus: &B) -> Result<u16, TransactionError<B>> {
+///     let sensor = Bme280::new(bus);
+///     sensor.ctrl().modify(|ctrl| ctrl | 0x01)?;
+///     sensor.temp().read()
+/// }
+/// ```
+///
+/// Registers are read with [`read_register`] and written with
+/// [`write_register`], so multi-byte values rely on the device
+/// auto-incrementing its register pointer.
+///
+/// [`read_register`]: crate::I2CBus::read_register
+/// [`write_register`]: crate::I2CBus::write_register
+#[macro_export]
+macro_rules! i2c_device {
+    (@register $(#[$meta:meta])* $field:ident $ty:ident $register:literal [$order:ty] [$access:ty] be $($rest:ident)*) => {
+        $crate::i2c_device!(@register $(#[$meta])* $field $ty $register [$crate::register_map::BigEndian] [$access] $($rest)*);
+    };
+    (@register $(#[$meta:meta])* $field:ident $ty:ident $register:literal [$order:ty] [$access:ty] le $($rest:ident)*) => {
+        $crate::i2c_device!(@register $(#[$meta])* $field $ty $register [$crate::register_map::LittleEndian] [$access] $($rest)*);
+    };
+    (@register $(#[$meta:meta])* $field:ident $ty:ident $register:literal [$order:ty] [$access:ty] r $($rest:ident)*) => {
+        $crate::i2c_device!(@register $(#[$meta])* $field $ty $register [$order] [$crate::register_map::ReadOnly] $($rest)*);
+    };
+    (@register $(#[$meta:meta])* $field:ident $ty:ident $register:literal [$order:ty] [$access:ty] w $($rest:ident)*) => {
+        $crate::i2c_device!(@register $(#[$meta])* $field $ty $register [$order] [$crate::register_map::WriteOnly] $($rest)*);
+    };
+    (@register $(#[$meta:meta])* $field:ident $ty:ident $register:literal [$order:ty] [$access:ty] rw $($rest:ident)*) => {
+        $crate::i2c_device!(@register $(#[$meta])* $field $ty $register [$order] [$crate::register_map::ReadWrite] $($rest)*);
+    };
+    (@register $(#[$meta:meta])* $field:ident $ty:ident $register:literal [$order:ty] [$access:ty] $other:ident $($rest:ident)*) => {
+        compile_error!(concat!("unknown register annotation `", stringify!($other), "`"));
+    };
+    (@register $(#[$meta:meta])* $field:ident $ty:ident $register:literal [$order:ty] [$access:ty]) => {
+        $(#[$meta])*
+        pub fn $field(&self) -> $crate::register_map::Register<'a, B, $ty, $order, $access> {
+            $crate::register_map::Register::new(
+                $crate::register_map::RegisterMap::new(self.map.bus(), self.map.address()),
+                $register,
+            )
+        }
+    };
+    (
+        $(#[$meta:meta])*
+        $vis:vis $name:ident at $address:literal {
+            $(
+                $(#[$field_meta:meta])*
+                $field:ident : $ty:ident @ $register:literal $($annotation:ident)*
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name<'a, B: $crate::I2CBus> {
+            map: $crate::register_map::RegisterMap<'a, B>,
+        }
+
+        impl<'a, B: $crate::I2CBus> $name<'a, B> {
+            /// The default address of the device
+            pub const ADDRESS: u8 = $address;
+
+            /// Creates a new driver for the device at its default address
+            pub const fn new(bus: &'a B) -> Self {
+                Self::with_address(bus, Self::ADDRESS)
+            }
+
+            /// Creates a new driver for the device at `address`
+            pub const fn with_address(bus: &'a B, address: u8) -> Self {
+                Self {
+                    map: $crate::register_map::RegisterMap::new(bus, address),
+                }
+            }
+
+            /// Returns the register map of the device
+            pub const fn map(&self) -> &$crate::register_map::RegisterMap<'a, B> {
+                &self.map
+            }
+
+            $(
+                $crate::i2c_device!(
+                    @register $(#[$field_meta])* $field $ty $register
+                    [$crate::register_map::BigEndian] [$crate::register_map::ReadWrite]
+                    $($annotation)*
+                );
+            )*
+        }
+    };
+}